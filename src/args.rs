@@ -1,8 +1,13 @@
 use std::path::PathBuf;
 
-use clap::{command, Parser, Subcommand};
+use clap::{Parser, Subcommand};
+use clap_complete::Shell;
+
+use pngme::chunk::CrcPolicy;
 
 const CT_HELP: &str = "块类型，由4位a-z、A-Z的字符组成，第三位需大写。例如: ruSt";
+const REPORT_HELP: &str = "将本次修改的审计报告（JSON）写入该文件";
+const BACKUP_HELP: &str = "覆盖原文件前不再备份到 <PATH>.bak";
 
 #[derive(Parser, Debug)]
 #[command(name = "pngme")]
@@ -12,11 +17,32 @@ const CT_HELP: &str = "块类型，由4位a-z、A-Z的字符组成，第三位
 pub struct Args {
     #[command(subcommand)]
     pub command: Option<Commands>,
+    #[arg(
+        long,
+        global = true,
+        help = "严格按照 PNG 规范解析，拒绝不合规的块类型"
+    )]
+    pub strict_spec: bool,
+    #[arg(
+        short,
+        long,
+        global = true,
+        help = "不输出状态信息（encode 的大小摘要、remove 等命令的结果、找不到块的提示），找不到块时以非 0 状态退出"
+    )]
+    pub quiet: bool,
+    #[arg(
+        long,
+        global = true,
+        help = "print、check、list、count 将文件映射到内存后解析，而不是先读入整个文件"
+    )]
+    pub mmap: bool,
 }
 
 #[derive(Subcommand, Debug)]
 pub enum Commands {
-    Encode {
+    Encode(Box<EncodeArgs>),
+    Decode(DecodeArgs),
+    Remove {
         path: PathBuf,
         #[arg(
             short,
@@ -24,21 +50,106 @@ pub enum Commands {
             help = CT_HELP
         )]
         chunk_type: String,
-        #[arg(short, long, help = "需要隐藏的信息")]
-        message: String,
+        #[arg(long, value_name = "PATH", help = REPORT_HELP)]
+        emit_report: Option<PathBuf>,
+        #[arg(
+            long,
+            help = "不检查文件中的块类型是否合法（例如包含数字），按 4 个字节原样匹配"
+        )]
+        no_validate_type: bool,
+        #[arg(long, help = "删除所有该类型的块，默认只删除第一个")]
+        all: bool,
+        #[arg(long, help = BACKUP_HELP)]
+        no_backup: bool,
+    },
+    Print {
+        path: PathBuf,
+        #[arg(long, help = "以 JSON 数组输出所有块的元数据")]
+        json: bool,
+        #[arg(long, help = "crc 不匹配时不中断解析，只报告出错的块")]
+        lenient: bool,
+    },
+    List {
+        path: PathBuf,
+        #[arg(
+            long,
+            value_name = "N",
+            help = "在每个块后预览数据的前 N 个字节（十六进制和 ASCII）"
+        )]
+        preview: Option<usize>,
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text, help = "输出格式")]
+        format: OutputFormat,
+    },
+    Dupes {
+        path: PathBuf,
+    },
+    Count {
+        path: PathBuf,
+        #[arg(
+            short,
+            long,
+            short_alias = 't',
+            help = "只输出该类型的块的数量，数量为 0 时以非 0 状态退出；默认按类型输出所有块的数量"
+        )]
+        chunk_type: Option<String>,
+    },
+    ExtractTrailingPng {
+        path: PathBuf,
         #[arg(short = 'o', long, help = "输出文件")]
-        output: Option<PathBuf>,
+        output: PathBuf,
     },
-    Decode {
+    Check {
         path: PathBuf,
+        #[arg(long, help = "只检查文件头是否为 PNG 签名，不解析块")]
+        signature_only: bool,
+        #[arg(
+            long,
+            conflicts_with = "signature_only",
+            help = "同时校验每个块的 crc，存在不匹配时以非 0 状态退出"
+        )]
+        verify_crc: bool,
+        #[arg(long, help = "crc 不匹配时不中断解析，只报告出错的块")]
+        lenient: bool,
         #[arg(
             short,
             long,
-            help = CT_HELP
+            visible_alias = "type",
+            help = "只检查该类型的块是否存在且有数据，默认检查所有非标准的私有辅助块"
         )]
-        chunk_type: String,
+        chunk_type: Option<String>,
     },
-    Remove {
+    Info {
+        path: PathBuf,
+    },
+    Validate {
+        path: PathBuf,
+    },
+    #[command(visible_alias = "text")]
+    ListText {
+        path: PathBuf,
+        #[arg(long, help = "按关键字分组输出")]
+        group_by_keyword: bool,
+    },
+    SetText {
+        path: PathBuf,
+        #[arg(short, long, help = "tEXt 关键字，1-79 个可打印的 Latin-1 字符")]
+        keyword: String,
+        #[arg(short, long, help = "tEXt 文本，需能以 Latin-1 表示")]
+        value: String,
+        #[arg(short = 'o', long, help = "输出文件，默认覆盖原文件")]
+        output: Option<PathBuf>,
+    },
+    #[command(visible_alias = "minify")]
+    Strip {
+        path: PathBuf,
+        #[arg(long, help = "只删除不可安全复制的辅助块，模拟图片编辑器的行为")]
+        unsafe_only: bool,
+        #[arg(long, value_name = "PATH", help = REPORT_HELP)]
+        emit_report: Option<PathBuf>,
+        #[arg(short = 'o', long, help = "输出文件，默认覆盖原文件")]
+        output: Option<PathBuf>,
+    },
+    Compact {
         path: PathBuf,
         #[arg(
             short,
@@ -46,13 +157,229 @@ pub enum Commands {
             help = CT_HELP
         )]
         chunk_type: String,
+        #[arg(short = 'o', long, help = "输出文件，默认覆盖原文件")]
+        output: Option<PathBuf>,
     },
-    Print {
+    #[command(visible_alias = "repair")]
+    RepairCrc {
+        path: PathBuf,
+        #[arg(short = 'o', long, help = "输出文件，默认覆盖原文件")]
+        output: Option<PathBuf>,
+    },
+    Manifest {
         path: PathBuf,
+        #[arg(short = 'o', long, help = "输出文件，默认输出到标准输出")]
+        output: Option<PathBuf>,
     },
-    Check {
+    Rebuild {
+        manifest: PathBuf,
+        #[arg(short = 'o', long, help = "输出文件")]
+        output: PathBuf,
+    },
+    Stats {
+        path: PathBuf,
+        #[arg(
+            long,
+            default_value_t = 7.0,
+            help = "熵（比特/字节）超过该值的辅助块会被标记为可疑"
+        )]
+        flag_threshold: f64,
+    },
+    Diff {
+        #[arg(help = "修改前的文件")]
+        a: PathBuf,
+        #[arg(help = "修改后的文件")]
+        b: PathBuf,
+    },
+    ExtractAll {
         path: PathBuf,
+        #[arg(help = "输出目录，不存在时会自动创建")]
+        out_dir: PathBuf,
     },
+    Completions {
+        #[arg(value_enum, help = "生成哪种 shell 的补全脚本")]
+        shell: Shell,
+    },
+}
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct EncodeArgs {
+    pub path: PathBuf,
+    #[arg(
+        short,
+        long,
+        required_unless_present = "interactive",
+        help = CT_HELP
+    )]
+    pub chunk_type: Option<String>,
+    #[command(flatten)]
+    pub message: MessageArgs,
+    #[arg(short = 'o', long, help = "输出文件，为 - 时写入标准输出")]
+    pub output: Option<PathBuf>,
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = ReplaceCrcPolicy::Recompute,
+        help = "替换已存在的块时，是否重新计算 crc"
+    )]
+    pub replace_crc_policy: ReplaceCrcPolicy,
+    #[arg(short, long, help = "替换已存在的块时不再询问")]
+    pub yes: bool,
+    #[arg(long, help = "即使文件中有多个 IHDR 或 IEND 也继续写入")]
+    pub force: bool,
+    #[arg(
+        long,
+        value_name = "CMD",
+        help = "写入后调用外部校验程序（如 pngcheck），输出文件路径作为最后一个参数"
+    )]
+    pub verify_with: Option<String>,
+    #[arg(long, help = "写入前使用 zlib 压缩信息，decode 时会自动解压")]
+    pub compress: bool,
+    #[arg(
+        long,
+        value_name = "0-9",
+        default_value_t = 6,
+        value_parser = clap::value_parser!(u32).range(0..=9),
+        requires = "compress",
+        help = "--compress 的压缩级别，0 只存储不压缩，9 压缩率最高"
+    )]
+    pub compress_level: u32,
+    #[arg(
+        long,
+        value_name = "SECS",
+        help = "读取 --message-file 或 --stdin 的超时时间（秒），超时后报错而不是一直等待"
+    )]
+    pub read_timeout: Option<u64>,
+    #[arg(long, value_name = "PATH", help = REPORT_HELP)]
+    pub emit_report: Option<PathBuf>,
+    #[arg(long, value_name = "PASS", help = "使用口令加密信息")]
+    pub password: Option<String>,
+    #[arg(long, help = "将块插入到第一个 IDAT 之前，而不是 IEND 之前")]
+    pub before_idat: bool,
+    #[arg(
+        long,
+        value_name = "INDEX",
+        conflicts_with = "before_idat",
+        help = "将块插入到指定位置（IHDR 之后、IEND 之前），默认插入到 IEND 之前"
+    )]
+    pub at: Option<usize>,
+    #[arg(
+        long,
+        value_name = "TYPE",
+        conflicts_with_all = ["before_idat", "at", "before"],
+        help = "将块插入到第一个该类型的块之后"
+    )]
+    pub after: Option<String>,
+    #[arg(
+        long,
+        value_name = "TYPE",
+        conflicts_with_all = ["before_idat", "at"],
+        help = "将块插入到第一个该类型的块之前"
+    )]
+    pub before: Option<String>,
+    #[arg(
+        long,
+        value_name = "BYTES",
+        help = "将信息按该大小拆分到多个同类型的块中，每个块带有序号头，decode 时会自动拼接"
+    )]
+    pub split: Option<usize>,
+    #[arg(long, help = BACKUP_HELP)]
+    pub no_backup: bool,
+    #[arg(long, help = "删除 IEND 之后的数据，默认原样保留")]
+    pub strip_trailing: bool,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct DecodeArgs {
+    pub path: PathBuf,
+    #[arg(
+        short,
+        long,
+        help = CT_HELP
+    )]
+    pub chunk_type: String,
+    #[arg(long, help = "存在多个相同类型的块时报错，而不是全部输出")]
+    pub paranoid: bool,
+    #[arg(long, help = "只输出第一个匹配的块")]
+    pub first: bool,
+    #[arg(
+        short = 'o',
+        long,
+        visible_alias = "output-file",
+        help = "将第一个匹配的块的原始数据写入文件，而不是打印"
+    )]
+    pub output: Option<PathBuf>,
+    #[arg(
+        long,
+        conflicts_with = "output",
+        help = "将第一个匹配的块的原始数据直接写入标准输出，不做任何转换"
+    )]
+    pub raw: bool,
+    #[arg(long, help = "要求信息是经过 --compress 压缩的，否则报错")]
+    pub decompress: bool,
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = DataEncoding::Utf8,
+        visible_alias = "format",
+        conflicts_with_all = ["output", "raw"],
+        help = "输出块数据的编码方式，hex 和 base64 直接输出块中的原始数据，不解密也不解压"
+    )]
+    pub encoding: DataEncoding,
+    #[arg(long, value_name = "PASS", help = "使用口令解密信息")]
+    pub password: Option<String>,
+    #[arg(
+        long,
+        help = "不检查文件中的块类型是否合法（例如包含数字），按 4 个字节原样匹配"
+    )]
+    pub no_validate_type: bool,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DataEncoding {
+    Utf8,
+    Hex,
+    Base64,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputFormat {
+    Text,
+    Json,
+}
+
+/// 命令行中的 `CrcPolicy`，库本身不依赖 clap
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum ReplaceCrcPolicy {
+    #[default]
+    Recompute,
+    Keep,
+}
+
+impl From<ReplaceCrcPolicy> for CrcPolicy {
+    fn from(policy: ReplaceCrcPolicy) -> Self {
+        match policy {
+            ReplaceCrcPolicy::Recompute => CrcPolicy::Recompute,
+            ReplaceCrcPolicy::Keep => CrcPolicy::Keep,
+        }
+    }
+}
+
+/// 需要隐藏的信息的来源，必须且只能指定一个
+#[derive(clap::Args, Debug, Clone)]
+#[group(required = true, multiple = false)]
+pub struct MessageArgs {
+    #[arg(short, long, help = "需要隐藏的信息")]
+    pub message: Option<String>,
+    #[arg(long, help = "从文件读取需要隐藏的信息，按原始字节写入")]
+    pub message_file: Option<PathBuf>,
+    #[arg(long, help = "从标准输入读取需要隐藏的信息，直到 EOF")]
+    pub stdin: bool,
+    #[arg(
+        long,
+        help = "在终端中依次询问块类型、信息和输出文件，只能在终端中使用"
+    )]
+    pub interactive: bool,
 }
 
 impl Args {}
@@ -1,20 +1,55 @@
 #![allow(unused)]
 
-use std::{fmt::Display, io::Read};
+use std::{
+    fmt::Display,
+    io::{Read, Write},
+};
 
+use base64::Engine;
 use crc::{Crc, CRC_32_ISO_HDLC};
 
-use crate::{chunk_type::ChunkType, Error, Result};
+use crate::{
+    chunk_type::ChunkType,
+    error::{ChunkError, PngError, PngResult},
+    png::Png,
+    Error, Result,
+};
+
+/// 修改 chunk 数据时对 crc 的处理策略
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CrcPolicy {
+    /// 根据新数据重新计算 crc
+    #[default]
+    Recompute,
+    /// 保留原有的 crc，即使它与新数据不再匹配
+    Keep,
+}
 
 #[derive(Debug, Clone)]
 pub struct Chunk {
     chunk_type: ChunkType,
     data: Vec<u8>,
+    crc: u32,
 }
 
 impl Chunk {
+    /// 长度字段只有 4 个字节，数据长度不能超过该值
+    pub const MAX_DATA_LEN: usize = u32::MAX as usize;
+
+    /// 与 `new` 相同，但数据长度超过 `MAX_DATA_LEN` 时报错，而不是写出错误的长度字段
+    pub fn try_new(chunk_type: ChunkType, data: Vec<u8>) -> PngResult<Self> {
+        check_data_len(data.len())?;
+        Ok(Self::new(chunk_type, data))
+    }
+
     pub fn new(chunk_type: ChunkType, data: Vec<u8>) -> Self {
-        Chunk { chunk_type, data }
+        let mut chunk = Chunk {
+            chunk_type,
+            data,
+            crc: 0,
+        };
+        chunk.crc = chunk.crc();
+        chunk
     }
 
     pub fn length(&self) -> usize {
@@ -29,11 +64,49 @@ impl Chunk {
         &self.chunk_type
     }
 
+    /// 块数据的原始字节，不做任何转换
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
     pub fn data_as_string(&self) -> Result<String> {
         Ok(String::from_utf8(self.data.as_slice().to_vec())?)
     }
 
-    fn crc(&self) -> u32 {
+    /// 小写十六进制表示的块数据
+    pub fn data_as_hex(&self) -> String {
+        self.data.iter().map(|b| format!("{b:02x}")).collect()
+    }
+
+    /// 标准 base64 表示的块数据
+    pub fn data_as_base64(&self) -> String {
+        base64::engine::general_purpose::STANDARD.encode(&self.data)
+    }
+
+    /// 写入文件的 crc，通常与 `crc()` 相同，除非使用 `CrcPolicy::Keep` 修改过数据
+    pub fn stored_crc(&self) -> u32 {
+        self.crc
+    }
+
+    /// 替换块数据，数据长度超过 `MAX_DATA_LEN` 时报错
+    pub fn set_data(&mut self, data: Vec<u8>, policy: CrcPolicy) -> PngResult<()> {
+        check_data_len(data.len())?;
+        self.data = data;
+        if policy == CrcPolicy::Recompute {
+            self.crc = self.crc();
+        }
+        Ok(())
+    }
+
+    /// 用重新计算的 crc 替换保存的 crc，返回保存的 crc 是否发生了变化
+    pub fn recompute_crc(&mut self) -> bool {
+        let crc = self.crc();
+        let changed = crc != self.crc;
+        self.crc = crc;
+        changed
+    }
+
+    pub fn crc(&self) -> u32 {
         Self::checksum(
             &self
                 .chunk_type
@@ -51,24 +124,54 @@ impl Chunk {
             .iter()
             .chain(self.chunk_type.bytes().iter())
             .chain(self.data.iter())
-            .chain(self.crc().to_be_bytes().iter())
+            .chain(self.crc.to_be_bytes().iter())
             .copied()
             .collect()
     }
 
-    pub fn checksum(bytes: &[u8]) -> u32 {
-        let crc = Crc::<u32>::new(&CRC_32_ISO_HDLC);
-        crc.checksum(bytes)
+    /// 将块直接写入 writer，结果与 `as_bytes` 相同，但不需要复制块数据
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> PngResult<()> {
+        writer.write_all(&(self.length() as u32).to_be_bytes())?;
+        writer.write_all(&self.chunk_type.bytes())?;
+        writer.write_all(&self.data)?;
+        writer.write_all(&self.crc.to_be_bytes())?;
+        Ok(())
     }
-}
 
-impl TryFrom<&[u8]> for Chunk {
-    type Error = Error;
+    /// 重新计算 crc，并与块中保存的 crc 比较
+    pub fn is_crc_valid(&self) -> bool {
+        self.crc() == self.crc
+    }
+
+    /// 检查块类型是否符合规范，以及数据长度能否写入 4 字节的长度字段
+    pub fn validate(&self) -> PngResult<()> {
+        if !self.chunk_type.is_valid() {
+            return Err(PngError::NonConformingChunkType(
+                self.chunk_type.to_string(),
+            ));
+        }
+        check_data_len(self.length())
+    }
+
+    /// 与 `TryFrom<&[u8]>` 相同，但 crc 不匹配时不会报错，
+    /// 保留原始的 crc 以便通过 `is_crc_valid` 检查
+    pub fn try_from_lenient(value: &[u8]) -> PngResult<Self> {
+        Self::parse(value, false, true)
+    }
+
+    /// 与 `TryFrom<&[u8]>` 相同，但不检查块类型是否由字母组成，
+    /// 用于处理块类型损坏的文件
+    pub fn try_from_unchecked_type(value: &[u8]) -> PngResult<Self> {
+        Self::parse(value, true, false)
+    }
 
     /// &[u8] 包含数据 [长度、chunk_type、数据、crc]
-    fn try_from(mut value: &[u8]) -> Result<Self> {
+    fn parse(mut value: &[u8], check_crc: bool, validate_type: bool) -> PngResult<Self> {
         if value.len() < 4 {
-            return Err(Error::from("incorrect chunk data"));
+            return Err(PngError::ChunkTooShort {
+                needed: 4,
+                actual: value.len(),
+            });
         }
 
         // 将 length 从 value 中分割出来
@@ -79,13 +182,20 @@ impl TryFrom<&[u8]> for Chunk {
         // 判断 value 的数据长度是否符合规范
         // chunk_type + data + crc
         if value.len() < length + 4 + 4 {
-            return Err(Error::from("incorrect chunk data"));
+            return Err(PngError::ChunkTooShort {
+                needed: 4 + length + 4 + 4,
+                actual: 4 + value.len(),
+            });
         }
 
         // 将 chuank_type 从 value 中分割出来
         let mut chunk = [0; 4];
         let _ = value.read(&mut chunk)?;
-        let chunk_type = ChunkType::try_from(chunk)?;
+        let chunk_type = if validate_type {
+            ChunkType::try_from(chunk)?
+        } else {
+            ChunkType::from_bytes_unchecked(chunk)
+        };
 
         // 将 data 从 value 中分割出来
         let mut data = vec![0; length];
@@ -96,16 +206,44 @@ impl TryFrom<&[u8]> for Chunk {
         let _ = value.read(&mut raw_crc_array)?;
         let raw_crc = u32::from_be_bytes(raw_crc_array);
 
-        let a: Vec<u8> = chunk.iter().chain(data.iter()).copied().collect();
-        let crc = Chunk::checksum(&a);
-        if crc != raw_crc {
-            return Err(Error::from("error"));
+        let chunk = Chunk {
+            chunk_type,
+            data,
+            crc: raw_crc,
+        };
+        if check_crc && !chunk.is_crc_valid() {
+            return Err(PngError::CrcMismatch {
+                expected: chunk.crc(),
+                actual: chunk.crc,
+            });
         }
 
-        Ok(Chunk { chunk_type, data })
+        Ok(chunk)
+    }
+
+    pub fn checksum(bytes: &[u8]) -> u32 {
+        let crc = Crc::<u32>::new(&CRC_32_ISO_HDLC);
+        crc.checksum(bytes)
     }
 }
 
+impl TryFrom<&[u8]> for Chunk {
+    type Error = PngError;
+
+    fn try_from(value: &[u8]) -> PngResult<Self> {
+        Self::parse(value, true, true)
+    }
+}
+
+/// 只比较块类型和数据，保存的 crc 不参与比较
+impl PartialEq for Chunk {
+    fn eq(&self, other: &Self) -> bool {
+        self.chunk_type == other.chunk_type && self.data == other.data
+    }
+}
+
+impl Eq for Chunk {}
+
 impl Display for Chunk {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "Chunk {{")?;
@@ -118,6 +256,159 @@ impl Display for Chunk {
     }
 }
 
+/// 逐个读取块的迭代器，每次只在内存中保留一个块。
+/// 出错时返回带有块序号和偏移的错误，之后不再继续读取。
+/// IEND 之后无法解析为块的字节不视为错误，而是保存在 `trailing_data` 中
+pub struct ChunkReader<R> {
+    reader: R,
+    options: ParseOptions,
+    index: usize,
+    offset: usize,
+    seen_iend: bool,
+    done: bool,
+    trailing: Vec<u8>,
+}
+
+impl<R: Read> ChunkReader<R> {
+    /// reader 需要位于第一个块的开头（PNG 签名之后），
+    /// 读取 PNG 文件时使用 `Png::chunk_reader`
+    pub fn new(reader: R) -> Self {
+        Self::with_options(reader, 0, ParseOptions::default())
+    }
+
+    /// offset 为 reader 当前位置在文件中的偏移，只用于错误信息
+    pub(crate) fn with_options(reader: R, offset: usize, options: ParseOptions) -> Self {
+        ChunkReader {
+            reader,
+            options,
+            index: 0,
+            offset,
+            seen_iend: false,
+            done: false,
+            trailing: Vec::new(),
+        }
+    }
+
+    /// IEND 之后的非块数据，读取完所有块后才完整
+    pub fn trailing_data(&self) -> &[u8] {
+        &self.trailing
+    }
+
+    pub fn into_trailing_data(self) -> Vec<u8> {
+        self.trailing
+    }
+
+    /// 将已读取的 bytes 以及 reader 中剩余的所有字节保存为尾部数据
+    fn read_trailing(&mut self, bytes: &[u8]) -> PngResult<Option<Chunk>> {
+        self.trailing.extend_from_slice(bytes);
+        self.reader.read_to_end(&mut self.trailing)?;
+        Ok(None)
+    }
+
+    fn read_chunk(&mut self) -> PngResult<Option<Chunk>> {
+        // 读取 length + chunk_type，读到 EOF 说明所有块都已读完
+        let mut head = [0; 8];
+        let n = read_full(&mut self.reader, &mut head)?;
+        if n == 0 {
+            return Ok(None);
+        }
+        // IEND 之后拼接的另一个 PNG 同样作为尾部数据保留
+        if self.seen_iend && (n < head.len() || head == Png::STANDARD_HEADER) {
+            return self.read_trailing(&head[..n]);
+        }
+        let (index, offset) = (self.index, self.offset);
+        let wrap = |source| ChunkError::wrap(index, &head[4..n.max(4)], offset, source);
+        if n < head.len() {
+            return Err(wrap(PngError::ChunkTooShort {
+                needed: head.len(),
+                actual: n,
+            }));
+        }
+
+        // 读取 data + crc，使用 take 避免按损坏的长度字段预先分配内存
+        let length = u32::from_be_bytes([head[0], head[1], head[2], head[3]]) as usize;
+        let mut bytes = head.to_vec();
+        (&mut self.reader)
+            .take(length as u64 + 4)
+            .read_to_end(&mut bytes)?;
+
+        let chunk = match Chunk::parse(
+            bytes.as_slice(),
+            self.options.check_crc,
+            self.options.validate_type,
+        ) {
+            Ok(chunk) => chunk,
+            Err(_) if self.seen_iend => return self.read_trailing(&bytes),
+            Err(e) => return Err(wrap(e)),
+        };
+        if self.options.strict && !chunk.chunk_type().is_valid() {
+            return Err(wrap(PngError::NonConformingChunkType(
+                chunk.chunk_type().to_string(),
+            )));
+        }
+        self.seen_iend |= chunk.chunk_type() == "IEND";
+        self.index += 1;
+        self.offset += chunk.chunk_length();
+        Ok(Some(chunk))
+    }
+}
+
+impl<R: Read> Iterator for ChunkReader<R> {
+    type Item = PngResult<Chunk>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let result = self.read_chunk().transpose();
+        if !matches!(result, Some(Ok(_))) {
+            self.done = true;
+        }
+        result
+    }
+}
+
+/// 解析块的选项，默认与 `TryFrom<&[u8]>` 的行为相同
+#[derive(Clone, Copy)]
+pub(crate) struct ParseOptions {
+    /// 拒绝不符合 PNG 规范的块类型
+    pub(crate) strict: bool,
+    pub(crate) check_crc: bool,
+    /// 要求块类型由 4 个字母组成
+    pub(crate) validate_type: bool,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        ParseOptions {
+            strict: false,
+            check_crc: true,
+            validate_type: true,
+        }
+    }
+}
+
+/// 尽可能读满 buf，返回实际读取的字节数，只有遇到 EOF 时才会小于 buf 的长度
+pub(crate) fn read_full<R: Read>(reader: &mut R, buf: &mut [u8]) -> std::io::Result<usize> {
+    let mut read = 0;
+    while read < buf.len() {
+        match reader.read(&mut buf[read..]) {
+            Ok(0) => break,
+            Ok(n) => read += n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(read)
+}
+
+fn check_data_len(len: usize) -> PngResult<()> {
+    if len > Chunk::MAX_DATA_LEN {
+        return Err(PngError::DataTooLong(len));
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -227,6 +518,100 @@ mod tests {
         assert!(chunk.is_err());
     }
 
+    #[test]
+    fn test_crc_mismatch_error() {
+        let mut chunk_data = testing_chunk().as_bytes();
+        let last = chunk_data.len() - 1;
+        chunk_data[last] ^= 1;
+
+        match Chunk::try_from(chunk_data.as_ref()) {
+            Err(PngError::CrcMismatch { expected, actual }) => {
+                assert_eq!(expected, 2882656334);
+                assert_eq!(actual, 2882656334 ^ 1);
+            }
+            other => panic!("expected a CRC mismatch, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_lenient_chunk_from_bytes() {
+        let data_length: u32 = 42;
+        let chunk_type = "RuSt".as_bytes();
+        let message_bytes = "This is where your secret message will be!".as_bytes();
+        let crc: u32 = 2882656333;
+
+        let chunk_data: Vec<u8> = data_length
+            .to_be_bytes()
+            .iter()
+            .chain(chunk_type.iter())
+            .chain(message_bytes.iter())
+            .chain(crc.to_be_bytes().iter())
+            .copied()
+            .collect();
+
+        let chunk = Chunk::try_from_lenient(chunk_data.as_ref()).unwrap();
+
+        assert!(!chunk.is_crc_valid());
+        assert_eq!(chunk.stored_crc(), 2882656333);
+        assert_eq!(chunk.crc(), 2882656334);
+        assert_eq!(chunk.as_bytes(), chunk_data);
+    }
+
+    #[test]
+    fn test_chunk_crc_is_valid() {
+        let chunk = testing_chunk();
+        assert!(chunk.is_crc_valid());
+    }
+
+    #[test]
+    fn test_set_data_recompute_crc() {
+        let mut chunk = testing_chunk();
+        chunk
+            .set_data(b"new message".to_vec(), CrcPolicy::Recompute)
+            .unwrap();
+        assert_eq!(chunk.stored_crc(), chunk.crc());
+        assert_ne!(chunk.stored_crc(), 2882656334);
+    }
+
+    #[test]
+    fn test_set_data_keep_crc() {
+        let mut chunk = testing_chunk();
+        chunk
+            .set_data(b"new message".to_vec(), CrcPolicy::Keep)
+            .unwrap();
+        assert_eq!(chunk.stored_crc(), 2882656334);
+        assert_ne!(chunk.crc(), 2882656334);
+        assert_eq!(
+            &chunk.as_bytes()[chunk.chunk_length() - 4..],
+            &2882656334u32.to_be_bytes()
+        );
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(testing_chunk().validate().is_ok());
+
+        let chunk_type = ChunkType::from_str("Rust").unwrap();
+        let chunk = Chunk::new(chunk_type, b"reserved bit set".to_vec());
+        assert!(matches!(
+            chunk.validate(),
+            Err(PngError::NonConformingChunkType(_))
+        ));
+    }
+
+    #[test]
+    fn test_chunk_eq() {
+        let chunk_type = ChunkType::from_str("RuSt").unwrap();
+        let data = "This is where your secret message will be!"
+            .as_bytes()
+            .to_vec();
+        let chunk = Chunk::new(chunk_type, data);
+        assert_eq!(chunk, testing_chunk());
+
+        let other = Chunk::new(chunk_type, b"Another message".to_vec());
+        assert_ne!(chunk, other);
+    }
+
     #[test]
     pub fn test_chunk_trait_impls() {
         let data_length: u32 = 42;
@@ -247,4 +632,68 @@ mod tests {
 
         let _chunk_string = format!("{}", chunk);
     }
+
+    #[test]
+    fn test_data() {
+        let chunk_type = ChunkType::from_str("ruSt").unwrap();
+        let data = vec![0x00, 0xff, 0xfe, b'h', b'i'];
+        let chunk = Chunk::new(chunk_type, data.clone());
+        assert_eq!(chunk.data(), data.as_slice());
+    }
+
+    #[test]
+    fn test_data_as_hex_and_base64() {
+        let chunk_type = ChunkType::from_str("ruSt").unwrap();
+        let chunk = Chunk::new(chunk_type, vec![0x00, 0xab, b'h', b'i']);
+        assert_eq!(chunk.data_as_hex(), "00ab6869");
+        assert_eq!(chunk.data_as_base64(), "AKtoaQ==");
+    }
+
+    #[test]
+    fn test_try_new() {
+        let chunk_type = ChunkType::from_str("ruSt").unwrap();
+        let chunk = Chunk::try_new(chunk_type, b"message".to_vec()).unwrap();
+        assert_eq!(chunk, Chunk::new(chunk_type, b"message".to_vec()));
+
+        assert!(check_data_len(Chunk::MAX_DATA_LEN).is_ok());
+    }
+
+    // 只有 64 位平台上 usize 才能超过 u32::MAX，这里不实际分配 4GB 的数据
+    #[cfg(target_pointer_width = "64")]
+    #[test]
+    fn test_data_too_long() {
+        let len = Chunk::MAX_DATA_LEN + 1;
+        assert!(matches!(
+            check_data_len(len),
+            Err(PngError::DataTooLong(l)) if l == len
+        ));
+    }
+
+    #[test]
+    fn test_chunk_reader() {
+        let chunk =
+            |ty: &str, data: &[u8]| Chunk::new(ChunkType::from_str(ty).unwrap(), data.to_vec());
+        let mut bytes: Vec<u8> = [chunk("IHDR", b"header"), chunk("IDAT", b"data")]
+            .iter()
+            .flat_map(Chunk::as_bytes)
+            .collect();
+        // IDAT 之后是一个 crc 错误的块
+        let mut broken = chunk("ruSt", b"broken").as_bytes();
+        *broken.last_mut().unwrap() ^= 0xff;
+        bytes.extend(&broken);
+
+        let mut reader = ChunkReader::new(bytes.as_slice());
+        let idat = reader
+            .find(|c| c.as_ref().is_ok_and(|c| c.chunk_type() == "IDAT"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(idat.data(), b"data");
+
+        // 只有继续读取时才会遇到损坏的块，出错后迭代结束
+        let err = reader.next().unwrap().unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("chunk 2 (ruSt) at offset 34: CRC mismatch"));
+        assert!(reader.next().is_none());
+    }
 }
@@ -1,14 +1,48 @@
 #![allow(unused)]
-use std::{fmt::Display, io::Read, str::FromStr};
+use std::{fmt::Display, str::FromStr};
 
-use crate::{Error, Result};
+use crate::error::{PngError, PngResult};
 
-#[derive(PartialEq, Eq, Debug, Clone)]
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Clone, Copy)]
 pub struct ChunkType {
     chunk: [u8; 4],
 }
 
 impl ChunkType {
+    /// PNG 规范（以及常见扩展）中定义的块类型
+    pub const STANDARD_TYPES: [&'static str; 25] = [
+        "IHDR", "PLTE", "IDAT", "IEND", "tRNS", "cHRM", "gAMA", "iCCP", "sBIT", "sRGB", "cICP",
+        "mDCV", "cLLI", "tEXt", "zTXt", "iTXt", "bKGD", "hIST", "pHYs", "sPLT", "eXIf", "tIME",
+        "acTL", "fcTL", "fdAT",
+    ];
+
+    /// 是否为 `STANDARD_TYPES` 中的块类型
+    pub fn is_standard(&self) -> bool {
+        Self::STANDARD_TYPES.iter().any(|known| self == known)
+    }
+
+    /// 在已知的块类型中查找与 input 最接近的一个，用于提示拼写错误
+    pub fn suggest(input: &str) -> Option<&'static str> {
+        if let Some(known) = Self::STANDARD_TYPES
+            .iter()
+            .find(|known| known.eq_ignore_ascii_case(input))
+        {
+            return (*known != input).then_some(*known);
+        }
+
+        Self::STANDARD_TYPES
+            .iter()
+            .map(|known| (levenshtein(input, known), *known))
+            .filter(|(distance, _)| (1..=2).contains(distance))
+            .min_by_key(|(distance, _)| *distance)
+            .map(|(_, known)| known)
+    }
+
+    /// 不做任何检查，直接使用 4 个字节构造块类型，用于匹配文件中损坏的块类型
+    pub fn from_bytes_unchecked(bytes: [u8; 4]) -> Self {
+        ChunkType { chunk: bytes }
+    }
+
     pub fn bytes(&self) -> [u8; 4] {
         self.chunk
     }
@@ -25,25 +59,35 @@ impl ChunkType {
     ///     http://www.libpng.org/pub/png/spec/1.2/PNG-Structure.html
     ///     3.3. Chunk naming conventions
     ///         Ancillary bit: bit 5 of first byte
-    fn is_critical(&self) -> bool {
+    pub fn is_critical(&self) -> bool {
         // 0 (uppercase) = critical, 1 (lowercase) = ancillary.
         self.chunk[0] >> 5 & 1 == 0
     }
 
+    /// `is_critical` 的反义
+    pub fn is_ancillary(&self) -> bool {
+        !self.is_critical()
+    }
+
     /// Reference:
     ///     http://www.libpng.org/pub/png/spec/1.2/PNG-Structure.html
     ///     3.3. Chunk naming conventions
     ///         Private bit: bit 5 of second byte
-    fn is_public(&self) -> bool {
+    pub fn is_public(&self) -> bool {
         // 0 (uppercase) = public, 1 (lowercase) = private.
         self.chunk[1] >> 5 & 1 == 0
     }
 
+    /// `is_public` 的反义
+    pub fn is_private(&self) -> bool {
+        !self.is_public()
+    }
+
     /// Reference:
     ///     http://www.libpng.org/pub/png/spec/1.2/PNG-Structure.html
     ///     3.3. Chunk naming conventions
     ///         Reserved bit: bit 5 of third byte
-    fn is_reserved_bit_valid(&self) -> bool {
+    pub fn is_reserved_bit_valid(&self) -> bool {
         // Must be 0 (uppercase) in files conforming to this version of PNG.
         self.chunk[2] >> 5 & 1 == 0
     }
@@ -52,36 +96,111 @@ impl ChunkType {
     ///     http://www.libpng.org/pub/png/spec/1.2/PNG-Structure.html
     ///     3.3. Chunk naming conventions
     ///         Safe-to-copy bit: bit 5 of fourth byte
-    fn is_safe_to_copy(&self) -> bool {
+    pub fn is_safe_to_copy(&self) -> bool {
         // 0 (uppercase) = unsafe to copy, 1 (lowercase) = safe to copy.
         self.chunk[3] >> 5 & 1 == 1
     }
 
-    fn is_valid(&self) -> bool {
+    pub fn is_valid(&self) -> bool {
         self.is_reserved_bit_valid() && Self::check_valid(&self.chunk)
     }
+
+    /// 私有、辅助、可安全复制且保留位合法的块类型的数量（26^4）
+    pub const PRIVATE_TYPE_COUNT: u64 = 26 * 26 * 26 * 26;
+
+    /// 按 seed 生成一个私有块类型：第一、二、四位为小写字母，第三位为大写字母，
+    /// 满足 `is_valid`，并且是辅助、私有、可安全复制的
+    pub fn generate_private(seed: u64) -> Self {
+        let mut n = seed % Self::PRIVATE_TYPE_COUNT;
+        let mut chunk = [0; 4];
+        for byte in chunk.iter_mut().rev() {
+            *byte = b'a' + (n % 26) as u8;
+            n /= 26;
+        }
+        chunk[2] = chunk[2].to_ascii_uppercase();
+        ChunkType { chunk }
+    }
+
+    /// 按字母顺序遍历所有可用的私有块类型，见 `generate_private`
+    pub fn all_valid_private() -> impl Iterator<Item = ChunkType> {
+        (0..Self::PRIVATE_TYPE_COUNT).map(Self::generate_private)
+    }
+
+    /// 把任意 4 个字母的大小写改为辅助、私有、保留位合法、可安全复制的块类型
+    /// （小写、小写、大写、小写），input 不是 4 个 ASCII 字母时返回 None
+    pub fn suggest_private(input: &str) -> Option<Self> {
+        let chunk_type = Self::from_str(input).ok()?;
+        let mut chunk = chunk_type
+            .with_ancillary(true)
+            .with_private(true)
+            .with_safe_to_copy(true)
+            .chunk;
+        chunk[2].make_ascii_uppercase();
+        Some(ChunkType { chunk })
+    }
+
+    /// 设置 ancillary 位（第一个字节），返回新的块类型
+    pub fn with_ancillary(&self, ancillary: bool) -> Self {
+        self.with_bit(0, ancillary)
+    }
+
+    /// 设置 private 位（第二个字节），返回新的块类型
+    pub fn with_private(&self, private: bool) -> Self {
+        self.with_bit(1, private)
+    }
+
+    /// 设置 safe-to-copy 位（第四个字节），返回新的块类型
+    pub fn with_safe_to_copy(&self, safe_to_copy: bool) -> Self {
+        self.with_bit(3, safe_to_copy)
+    }
+
+    /// 第 index 个字节的 bit 5 为 1 即小写字母
+    fn with_bit(&self, index: usize, set: bool) -> Self {
+        let mut chunk = self.chunk;
+        if set {
+            chunk[index] |= 1 << 5;
+        } else {
+            chunk[index] &= !(1 << 5);
+        }
+        ChunkType { chunk }
+    }
+}
+
+/// 两个字符串之间的编辑距离
+fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut curr = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let cost = usize::from(ca != *cb);
+            curr[j + 1] = (prev[j] + cost).min(prev[j + 1] + 1).min(curr[j] + 1);
+        }
+        prev = curr;
+    }
+    prev[b.len()]
 }
 
 impl TryFrom<[u8; 4]> for ChunkType {
-    type Error = Error;
-    fn try_from(value: [u8; 4]) -> Result<Self> {
+    type Error = PngError;
+    fn try_from(value: [u8; 4]) -> PngResult<Self> {
         if !Self::check_valid(&value) {
-            return Err(Error::from("incorrect chunk type"));
+            return Err(PngError::InvalidChunkType(
+                String::from_utf8_lossy(&value).to_string(),
+            ));
         }
         Ok(ChunkType { chunk: value })
     }
 }
 
 impl FromStr for ChunkType {
-    type Err = Error;
+    type Err = PngError;
 
-    fn from_str(s: &str) -> Result<Self> {
-        let mut s = s.as_bytes();
-        let mut chunk = [0; 4];
-        let size = s.read(&mut chunk)?;
-        if size != 4 {
-            return Err(Error::from("len not equal 4"));
-        }
+    fn from_str(s: &str) -> PngResult<Self> {
+        let chunk: [u8; 4] = s
+            .as_bytes()
+            .try_into()
+            .map_err(|_| PngError::InvalidChunkType(s.to_string()))?;
         ChunkType::try_from(chunk)
     }
 }
@@ -92,6 +211,31 @@ impl Display for ChunkType {
     }
 }
 
+/// 直接比较 4 个字节，长度不是 4 的字符串总是不相等
+impl PartialEq<str> for ChunkType {
+    fn eq(&self, other: &str) -> bool {
+        self.chunk == other.as_bytes()
+    }
+}
+
+impl PartialEq<&str> for ChunkType {
+    fn eq(&self, other: &&str) -> bool {
+        self == *other
+    }
+}
+
+impl PartialEq<ChunkType> for str {
+    fn eq(&self, other: &ChunkType) -> bool {
+        other == self
+    }
+}
+
+impl PartialEq<ChunkType> for &str {
+    fn eq(&self, other: &ChunkType) -> bool {
+        other == *self
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -137,6 +281,18 @@ mod tests {
         assert!(!chunk.is_public());
     }
 
+    #[test]
+    pub fn test_chunk_type_is_ancillary() {
+        assert!(ChunkType::from_str("ruSt").unwrap().is_ancillary());
+        assert!(!ChunkType::from_str("RuSt").unwrap().is_ancillary());
+    }
+
+    #[test]
+    pub fn test_chunk_type_is_private() {
+        assert!(ChunkType::from_str("RuSt").unwrap().is_private());
+        assert!(!ChunkType::from_str("RUSt").unwrap().is_private());
+    }
+
     #[test]
     pub fn test_chunk_type_is_reserved_bit_valid() {
         let chunk = ChunkType::from_str("RuSt").unwrap();
@@ -182,6 +338,22 @@ mod tests {
         assert_eq!(&chunk.to_string(), "RuSt");
     }
 
+    #[test]
+    pub fn test_chunk_type_suggest() {
+        assert_eq!(ChunkType::suggest("iHDR"), Some("IHDR"));
+        assert_eq!(ChunkType::suggest("IDAX"), Some("IDAT"));
+        assert_eq!(ChunkType::suggest("text"), Some("tEXt"));
+        assert_eq!(ChunkType::suggest("IHDR"), None);
+        assert_eq!(ChunkType::suggest("ruSt"), None);
+    }
+
+    #[test]
+    pub fn test_levenshtein() {
+        assert_eq!(levenshtein("IHDR", "IHDR"), 0);
+        assert_eq!(levenshtein("iHDR", "IHDR"), 1);
+        assert_eq!(levenshtein("kitten", "sitting"), 3);
+    }
+
     #[test]
     pub fn test_chunk_type_trait_impls() {
         let chunk_type_1: ChunkType = TryFrom::try_from([82, 117, 83, 116]).unwrap();
@@ -189,4 +361,98 @@ mod tests {
         let _chunk_string = format!("{}", chunk_type_1);
         let _are_chunks_equal = chunk_type_1 == chunk_type_2;
     }
+
+    #[test]
+    pub fn test_chunk_type_with_bits() {
+        let base = ChunkType::from_str("RUST").unwrap();
+        let chunk = base
+            .with_ancillary(true)
+            .with_private(true)
+            .with_safe_to_copy(true);
+        assert_eq!(&chunk.to_string(), "ruSt");
+        assert!(!chunk.is_critical());
+        assert!(!chunk.is_public());
+        assert!(chunk.is_safe_to_copy());
+        assert!(chunk.is_valid());
+
+        let chunk = chunk.with_ancillary(false).with_safe_to_copy(false);
+        assert_eq!(&chunk.to_string(), "RuST");
+        assert!(chunk.is_critical());
+        assert!(!chunk.is_safe_to_copy());
+    }
+
+    #[test]
+    pub fn test_generate_private() {
+        assert_eq!(&ChunkType::generate_private(0).to_string(), "aaAa");
+        assert_eq!(&ChunkType::generate_private(27).to_string(), "aaBb");
+        assert_eq!(
+            ChunkType::generate_private(ChunkType::PRIVATE_TYPE_COUNT),
+            ChunkType::generate_private(0)
+        );
+
+        let mut count = 0;
+        for chunk in ChunkType::all_valid_private() {
+            assert!(chunk.is_valid());
+            assert!(!chunk.is_critical());
+            assert!(!chunk.is_public());
+            assert!(chunk.is_reserved_bit_valid());
+            assert!(chunk.is_safe_to_copy());
+            count += 1;
+        }
+        assert_eq!(count, ChunkType::PRIVATE_TYPE_COUNT);
+    }
+
+    #[test]
+    pub fn test_chunk_type_suggest_private() {
+        for input in ["rust", "RUST", "RuSt", "ruSt", "rUsT"] {
+            let chunk = ChunkType::suggest_private(input).unwrap();
+            assert_eq!(&chunk.to_string(), "ruSt");
+            assert!(chunk.is_valid());
+            assert!(chunk.is_ancillary());
+            assert!(chunk.is_private());
+            assert!(chunk.is_safe_to_copy());
+        }
+        assert_eq!(ChunkType::suggest_private("ru5t"), None);
+        assert_eq!(ChunkType::suggest_private("rus"), None);
+    }
+
+    #[test]
+    pub fn test_chunk_type_hash_and_ord() {
+        use std::collections::HashSet;
+
+        let types: Vec<ChunkType> = ["ruSt", "IHDR", "ruSt", "IEND", "IHDR"]
+            .iter()
+            .map(|s| ChunkType::from_str(s).unwrap())
+            .collect();
+        let unique: HashSet<ChunkType> = types.iter().copied().collect();
+        assert_eq!(unique.len(), 3);
+
+        let mut sorted: Vec<ChunkType> = unique.into_iter().collect();
+        sorted.sort();
+        let sorted: Vec<String> = sorted.iter().map(ChunkType::to_string).collect();
+        assert_eq!(sorted, ["IEND", "IHDR", "ruSt"]);
+    }
+
+    #[test]
+    pub fn test_chunk_type_eq_str() {
+        let chunk_type = ChunkType::from_str("ruSt").unwrap();
+        assert!(chunk_type == "ruSt");
+        assert!("ruSt" == chunk_type);
+        assert!(&chunk_type == "ruSt");
+
+        assert!(chunk_type != "RuSt");
+        assert!("rust" != chunk_type);
+
+        assert!(chunk_type != "ruS");
+        assert!(chunk_type != "ruSt ");
+        assert!(chunk_type != "");
+    }
+
+    #[test]
+    pub fn test_chunk_type_is_standard() {
+        assert!(ChunkType::from_str("tEXt").unwrap().is_standard());
+        assert!(ChunkType::from_str("IDAT").unwrap().is_standard());
+        assert!(!ChunkType::from_str("ruSt").unwrap().is_standard());
+        assert!(!ChunkType::from_str("TEXT").unwrap().is_standard());
+    }
 }
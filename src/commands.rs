@@ -1,103 +1,635 @@
 use std::{
-    io::{Read, Write},
+    collections::{HashMap, HashSet},
+    io::{BufRead, IsTerminal, Read, Write},
     path::PathBuf,
+    sync::mpsc,
+    time::Duration,
 };
 
-use crate::{args, chunk::Chunk, chunk_type::ChunkType, png::Png, Result};
+use flate2::{read::ZlibDecoder, write::ZlibEncoder, Compression};
+use serde::Serialize;
 
-pub struct Commands;
+use pngme::{
+    chunk::Chunk,
+    chunk_type::ChunkType,
+    error::{PngError, PngResult},
+    png::{Png, Severity},
+    ChunkError, Error, Result,
+};
+
+use crate::{args, crypto, manifest::Manifest, report::Report};
+
+/// `decode`、`check` 以及 `--quiet` 下的 `remove`/`compact` 找不到隐藏信息时返回的错误，
+/// `main` 将其映射为退出码 2
+#[derive(Debug)]
+pub struct NotFound;
+
+impl std::fmt::Display for NotFound {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "no hidden message found")
+    }
+}
+
+impl std::error::Error for NotFound {}
+
+#[derive(Default)]
+pub struct Commands {
+    strict_spec: bool,
+    quiet: bool,
+    mmap: bool,
+}
 
 impl Commands {
+    pub fn new(strict_spec: bool, quiet: bool, mmap: bool) -> Self {
+        Commands {
+            strict_spec,
+            quiet,
+            mmap,
+        }
+    }
+
+    /// 输出面向用户的状态信息，`--quiet` 时不输出
+    fn status(&self, message: impl std::fmt::Display) {
+        if !self.quiet {
+            println!("{message}");
+        }
+    }
+
+    /// 输出写入结果后的提示；写到标准输出时改为标准错误，避免混入 PNG 数据
+    fn status_for(&self, output: &std::path::Path, message: impl std::fmt::Display) {
+        if is_stdio(output) {
+            if !self.quiet {
+                eprintln!("{message}");
+            }
+            return;
+        }
+        self.status(message);
+    }
+
+    /// 找不到指定类型的块时输出提示；`--quiet` 时不输出提示，而是以非 0 状态退出
+    fn not_found(&self, chunk_type: &str) -> Result<()> {
+        if self.quiet {
+            return Err(NotFound.into());
+        }
+        println!("{}", not_found_message(chunk_type));
+        Ok(())
+    }
+
     pub fn process(&self, args: args::Args) -> Result<()> {
         if let Some(command) = &args.command {
             match command {
-                args::Commands::Encode {
+                args::Commands::Encode(encode) => self.encode(encode)?,
+                args::Commands::Decode(decode) => self.decode(decode)?,
+                args::Commands::Remove {
+                    path,
+                    chunk_type,
+                    emit_report,
+                    no_validate_type,
+                    all,
+                    no_backup,
+                } => self.remove(
+                    path,
+                    chunk_type,
+                    emit_report.as_ref(),
+                    *no_validate_type,
+                    *all,
+                    *no_backup,
+                )?,
+                args::Commands::Print {
+                    path,
+                    json,
+                    lenient,
+                } => self.print(path, *json, *lenient)?,
+                args::Commands::List {
+                    path,
+                    preview,
+                    format,
+                } => self.list(path, *preview, *format)?,
+                args::Commands::Dupes { path } => self.dupes(path)?,
+                args::Commands::Count { path, chunk_type } => {
+                    self.count(path, chunk_type.as_deref())?
+                }
+                args::Commands::ExtractTrailingPng { path, output } => {
+                    self.extract_trailing_png(path, output)?
+                }
+                args::Commands::Check {
+                    path,
+                    signature_only,
+                    verify_crc,
+                    lenient,
+                    chunk_type,
+                } => self.check(
+                    path,
+                    *signature_only,
+                    *verify_crc,
+                    *lenient,
+                    chunk_type.as_deref(),
+                )?,
+                args::Commands::Info { path } => self.info(path)?,
+                args::Commands::Validate { path } => self.validate(path)?,
+                args::Commands::ListText {
+                    path,
+                    group_by_keyword,
+                } => self.list_text(path, *group_by_keyword)?,
+                args::Commands::Strip {
+                    path,
+                    unsafe_only,
+                    emit_report,
+                    output,
+                } => self.strip(path, *unsafe_only, emit_report.as_ref(), output.as_ref())?,
+                args::Commands::SetText {
+                    path,
+                    keyword,
+                    value,
+                    output,
+                } => self.set_text(path, keyword, value, output.as_ref())?,
+                args::Commands::Compact {
                     path,
                     chunk_type,
-                    message,
                     output,
-                } => self.encode(path, chunk_type, message, output)?,
-                args::Commands::Decode { path, chunk_type } => self.decode(path, chunk_type)?,
-                args::Commands::Remove { path, chunk_type } => self.remove(path, chunk_type)?,
-                args::Commands::Print { path } => self.print(path)?,
-                args::Commands::Check { path } => self.check(path)?,
+                } => self.compact(path, chunk_type, output.as_ref())?,
+                args::Commands::RepairCrc { path, output } => {
+                    self.repair_crc(path, output.as_ref())?
+                }
+                args::Commands::Manifest { path, output } => {
+                    self.manifest(path, output.as_ref())?
+                }
+                args::Commands::Rebuild { manifest, output } => self.rebuild(manifest, output)?,
+                args::Commands::Stats {
+                    path,
+                    flag_threshold,
+                } => self.stats(path, *flag_threshold)?,
+                args::Commands::Diff { a, b } => self.diff(a, b)?,
+                args::Commands::ExtractAll { path, out_dir } => self.extract_all(path, out_dir)?,
+                args::Commands::Completions { shell } => {
+                    write_completions(*shell, &mut std::io::stdout().lock())
+                }
             };
         }
         Ok(())
     }
 
+    /// 打开文件用于读取，路径为 `-` 时读取标准输入
+    fn open_file(&self, path: &PathBuf) -> Result<Box<dyn Read>> {
+        if is_stdio(path) {
+            return Ok(Box::new(std::io::stdin().lock()));
+        }
+        Ok(Box::new(std::fs::OpenOptions::new().read(true).open(path)?))
+    }
+
     fn read_file(&self, path: &PathBuf) -> Result<Vec<u8>> {
-        let mut file = std::fs::OpenOptions::new().read(true).open(path)?;
         let mut buf = Vec::new();
-        let _ = file.read_to_end(&mut buf)?;
+        let _ = self.open_file(path)?.read_to_end(&mut buf)?;
         Ok(buf)
     }
 
-    fn write_file(&self, path: &PathBuf, content: &[u8]) -> Result<()> {
-        let mut file = std::fs::OpenOptions::new()
+    /// 原地修改文件前将原文件复制到 `<path>.bak`，之后的步骤失败时备份仍会保留
+    fn backup(&self, path: &PathBuf) -> Result<()> {
+        if is_stdio(path) {
+            return Ok(());
+        }
+        std::fs::copy(path, backup_path(path))?;
+        Ok(())
+    }
+
+    /// 打开文件用于写入，路径为 `-` 时写入标准输出
+    fn create_file(&self, path: &PathBuf) -> Result<Box<dyn Write>> {
+        if is_stdio(path) {
+            return Ok(Box::new(std::io::stdout().lock()));
+        }
+        let file = std::fs::OpenOptions::new()
             .create(true)
             .write(true)
             .truncate(true)
             .open(path)?;
+        Ok(Box::new(std::io::BufWriter::new(file)))
+    }
+
+    /// 写入文件，路径为 `-` 时写入标准输出
+    fn write_file(&self, path: &PathBuf, content: &[u8]) -> Result<()> {
+        let mut file = self.create_file(path)?;
         file.write_all(content)?;
         file.flush()?;
         Ok(())
     }
 
+    /// 逐个块写入 PNG，不在内存中构造整个文件
+    fn write_png(&self, path: &PathBuf, png: &Png) -> Result<()> {
+        let mut file = self.create_file(path)?;
+        png.write_to(&mut file)?;
+        file.flush()?;
+        Ok(())
+    }
+
+    /// 读取需要隐藏的信息，指定 timeout 时读取超时会报错而不是一直阻塞（例如 FIFO 没有写入方）
+    fn read_message(
+        &self,
+        message: &args::MessageArgs,
+        timeout: Option<Duration>,
+    ) -> Result<Vec<u8>> {
+        if let Some(message) = &message.message {
+            return Ok(message.as_bytes().to_vec());
+        }
+        if let Some(path) = &message.message_file {
+            // 打开 FIFO 时也可能阻塞，所以打开文件同样放在超时范围内
+            let path = path.clone();
+            return read_with_timeout(timeout, move || std::fs::File::open(path));
+        }
+        if message.stdin {
+            return read_with_timeout(timeout, || Ok(std::io::stdin()));
+        }
+        Err(Error::from(
+            "one of `--message`, `--message-file`, `--stdin` or `--interactive` is required",
+        ))
+    }
+
+    /// 解析 PNG；需要写审计报告时同时返回修改前的文件内容。
+    /// 两者来自同一次读取，路径为 `-` 时不会重复读取标准输入
+    fn png_with_snapshot(
+        &self,
+        emit_report: Option<&PathBuf>,
+        path: &PathBuf,
+        validate_type: bool,
+    ) -> Result<(Png, Option<Vec<u8>>)> {
+        if emit_report.is_none() {
+            return Ok((self.png_from_file_with(path, validate_type)?, None));
+        }
+        let data = self.read_file(path)?;
+        let png = self.png_from_reader(data.as_slice(), validate_type)?;
+        Ok((png, Some(data)))
+    }
+
     fn png_from_file(&self, path: &PathBuf) -> Result<Png> {
-        let png_data = self.read_file(path)?;
-        Png::try_from(png_data.as_slice())
+        self.png_from_file_with(path, true)
+    }
+
+    fn png_from_reader(&self, reader: impl Read, validate_type: bool) -> Result<Png> {
+        let reader = std::io::BufReader::new(reader);
+        let png = if !validate_type {
+            Png::from_reader_unchecked_types(reader)?
+        } else if self.strict_spec {
+            Png::from_reader_strict(reader)?
+        } else {
+            Png::from_reader(reader)?
+        };
+        Ok(png)
+    }
+
+    /// `--mmap` 时将普通文件映射到内存，标准输入或未指定 `--mmap` 时返回 None
+    fn map_file(&self, path: &PathBuf) -> Result<Option<memmap2::Mmap>> {
+        if !self.mmap || is_stdio(path) {
+            return Ok(None);
+        }
+        let file = std::fs::File::open(path)?;
+        // SAFETY: 映射只读，调用方只在解析期间借用，解析出的块数据都会复制出来。
+        // 文件在映射期间被其他进程改写时，读到的字节可能前后不一致，最坏的结果是
+        // crc 校验失败或解析出错误的块，所有长度都有边界检查，不会越界读取；
+        // 但文件被截断时访问超出新长度的页会触发 SIGBUS，因此 `--mmap` 需要显式开启
+        Ok(Some(unsafe { memmap2::Mmap::map(&file)? }))
+    }
+
+    /// 只读命令使用的解析，`--mmap` 时直接从内存映射解析，crc 校验与 `png_from_file` 相同
+    fn png_for_reading(&self, path: &PathBuf) -> Result<Png> {
+        match self.map_file(path)? {
+            Some(map) if self.strict_spec => Ok(Png::try_from_strict(&map[..])?),
+            Some(map) => Ok(Png::try_from(&map[..])?),
+            None => self.png_from_file(path),
+        }
+    }
+
+    /// 与 `png_for_reading` 相同，但使用宽松解析
+    fn png_for_reading_lenient(&self, path: &PathBuf) -> Result<(Png, Vec<ChunkError>)> {
+        match self.map_file(path)? {
+            Some(map) => Ok(Png::try_from_lenient(&map[..])?),
+            None => self.png_from_file_lenient(path),
+        }
     }
 
-    fn encode<'a, 'b>(
+    /// 逐个读取块，按出现顺序返回满足条件的块的类型（不重复），每次只在内存中保留一个块。
+    /// stop_at_first 为 true 时找到第一个满足条件的块就停止读取
+    fn matching_types(
         &self,
-        mut path: &'a PathBuf,
-        chunk_type: &String,
-        message: &String,
-        output: &'b Option<PathBuf>,
-    ) -> Result<()>
-    where
-        'b: 'a,
-    {
-        let mut png = self.png_from_file(path)?;
-        if png.chunk_by_type(chunk_type).is_some() {
-            let _ = png.remove_chunk(chunk_type);
+        path: &PathBuf,
+        predicate: impl Fn(&Chunk) -> bool,
+        stop_at_first: bool,
+    ) -> Result<Vec<ChunkType>> {
+        match self.map_file(path)? {
+            Some(map) => self.matching_types_in(&map[..], predicate, stop_at_first),
+            None => {
+                let file = std::io::BufReader::new(self.open_file(path)?);
+                self.matching_types_in(file, predicate, stop_at_first)
+            }
+        }
+    }
+
+    fn matching_types_in(
+        &self,
+        reader: impl Read,
+        predicate: impl Fn(&Chunk) -> bool,
+        stop_at_first: bool,
+    ) -> Result<Vec<ChunkType>> {
+        let mut types = Vec::new();
+        for chunk in Png::chunk_reader(reader)? {
+            let chunk = chunk?;
+            if self.strict_spec && !chunk.chunk_type().is_valid() {
+                return Err(
+                    PngError::NonConformingChunkType(chunk.chunk_type().to_string()).into(),
+                );
+            }
+            if predicate(&chunk) && !types.contains(chunk.chunk_type()) {
+                types.push(*chunk.chunk_type());
+                if stop_at_first {
+                    break;
+                }
+            }
+        }
+        Ok(types)
+    }
+
+    /// validate_type 为 false 时不检查文件中的块类型是否合法
+    fn png_from_file_with(&self, path: &PathBuf, validate_type: bool) -> Result<Png> {
+        self.png_from_reader(self.open_file(path)?, validate_type)
+    }
+
+    /// 宽松解析，crc 不匹配的块会被保留，并在返回的错误列表中报告
+    fn png_from_file_lenient(&self, path: &PathBuf) -> Result<(Png, Vec<ChunkError>)> {
+        Ok(Png::try_from_lenient(&self.read_file(path)?)?)
+    }
+
+    fn encode(&self, args: &args::EncodeArgs) -> Result<()> {
+        if args.message.interactive {
+            if !std::io::stdin().is_terminal() {
+                return Err(Error::from("`--interactive` requires a terminal on stdin"));
+            }
+            let args =
+                prompt_encode_args(args, &mut std::io::stdin().lock(), &mut std::io::stdout())?;
+            return self.encode(&args);
+        }
+
+        let chunk_type = args
+            .chunk_type
+            .as_deref()
+            .ok_or_else(|| Error::from("`--chunk-type` is required"))?;
+        // 在读取文件之前检查块类型，避免拼写错误时白白读取大文件
+        let new_type = parse_chunk_type(chunk_type)?;
+        // 写到标准输出（`-o -`，或输入为 `-` 且没有 `-o`）时标准输出只允许出现 PNG 数据
+        let output = args.output.as_ref().unwrap_or(&args.path);
+        let to_stdout = is_stdio(output);
+        if to_stdout && args.verify_with.is_some() {
+            return Err(Error::from(
+                "`--verify-with` cannot be used when writing to stdout",
+            ));
+        }
+        let data = pack_message(
+            self.read_message(&args.message, args.read_timeout.map(Duration::from_secs))?,
+            args.compress.then(|| Compression::new(args.compress_level)),
+        )?;
+        let data = match &args.password {
+            Some(password) => crypto::encrypt(&data, password)?,
+            None => data,
+        };
+        let (mut png, before) =
+            self.png_with_snapshot(args.emit_report.as_ref(), &args.path, true)?;
+        let original_size = png.byte_len();
+        if let Some((duplicated, count)) = png.duplicated_singletons().first() {
+            if !args.force {
+                return Err(Error::from(format!(
+                    "refusing to modify {}: {duplicated} appears {count} times; use `--force` to write anyway",
+                    args.path.display()
+                )));
+            }
+        }
+
+        if let Some(existing) = png.chunk_by_type(chunk_type) {
+            let interactive =
+                !to_stdout && std::io::stdin().is_terminal() && std::io::stdout().is_terminal();
+            if !args.yes
+                && !confirm_overwrite(
+                    interactive,
+                    &existing,
+                    &mut std::io::stdin().lock(),
+                    &mut std::io::stdout(),
+                )?
+            {
+                if !self.quiet {
+                    eprintln!("`{chunk_type}` message kept");
+                }
+                return Ok(());
+            }
         }
 
-        let bytes: [u8; 4] = chunk_type.as_bytes().try_into()?;
-        let chunk_type = ChunkType::try_from(bytes)?;
-        let chunk = Chunk::new(chunk_type, message.as_bytes().to_vec());
+        let replaced: Vec<Chunk> = png
+            .chunks_by_type(chunk_type)
+            .into_iter()
+            .cloned()
+            .collect();
+        // 替换时保留第一个块（用于 crc 策略），同时删除其余重复的块
+        let existing = png.remove_first_chunk_by_type(chunk_type);
+        png.remove_all_chunks_by_type(chunk_type);
+        let chunks = match (args.split, existing) {
+            (Some(size), _) => split_message(&data, size)?
+                .into_iter()
+                .map(|fragment| Chunk::try_new(new_type, fragment))
+                .collect::<PngResult<Vec<_>>>()?,
+            (None, Some(mut chunk)) => {
+                chunk.set_data(data, args.replace_crc_policy.into())?;
+                vec![chunk]
+            }
+            (None, None) => vec![Chunk::try_new(new_type, data)?],
+        };
+
+        // --after/--before 转换为相对于第一个该类型的块的位置
+        let anchor = |anchor_type: &str| {
+            png.iter_chunks()
+                .position(|chunk| chunk.chunk_type() == anchor_type)
+                .ok_or_else(|| Error::from(format!("anchor chunk `{anchor_type}` not found")))
+        };
+        let at = match (&args.after, &args.before) {
+            (Some(after), _) => Some(anchor(after)? + 1),
+            (None, Some(before)) => Some(anchor(before)?),
+            (None, None) => args.at,
+        };
+
+        // 拆分后的块按顺序相邻插入
+        for (offset, chunk) in chunks.iter().enumerate() {
+            if let Some(index) = at {
+                png.insert_chunk(index + offset, chunk.clone())?;
+            } else if args.before_idat {
+                png.insert_before_idat(chunk.clone());
+            } else {
+                png.append_chunk(chunk.clone());
+            }
+        }
 
-        png.append_chunk(chunk);
+        if args.strip_trailing {
+            png.strip_trailing_data();
+        }
 
-        if let Some(p) = output {
-            path = p;
+        if !args.no_backup && output == &args.path {
+            self.backup(&args.path)?;
+        }
+        self.write_png(output, &png)?;
+        if let (Some(report), Some(before)) = (&args.emit_report, before) {
+            Report::new(&args.path, &before, output, &png.as_bytes())
+                .added(&chunks)
+                .removed(&replaced)
+                .write(report)?;
         }
-        self.write_file(path, &png.as_bytes())?;
+        if let Some(validator) = &args.verify_with {
+            self.verify_with(validator, output)?;
+        }
+        self.status_for(
+            output,
+            encode_summary(chunk_type, &chunks, original_size, png.byte_len(), output),
+        );
         Ok(())
     }
 
-    fn decode(&self, path: &PathBuf, chunk_type: &String) -> Result<()> {
-        let png = self.png_from_file(path)?;
-        if let Some(chunk) = png.chunk_by_type(chunk_type) {
-            println!("{}", chunk.data_as_string()?);
-        } else {
-            println!("`{chunk_type}` message not exists")
+    /// 调用外部程序校验写入的文件，程序以非 0 状态退出时报错
+    fn verify_with(&self, validator: &str, path: &PathBuf) -> Result<()> {
+        let mut parts = validator.split_whitespace();
+        let program = parts
+            .next()
+            .ok_or_else(|| Error::from("`--verify-with` command is empty"))?;
+        let output = std::process::Command::new(program)
+            .args(parts)
+            .arg(path)
+            .output()?;
+        if !output.status.success() {
+            return Err(Error::from(format!(
+                "`{validator}` rejected {} ({}): {}",
+                path.display(),
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
         }
         Ok(())
     }
 
-    fn remove(&self, path: &PathBuf, chunk_type: &String) -> Result<()> {
-        let mut png = self.png_from_file(path)?;
-        if png.remove_chunk(chunk_type).is_some() {
-            self.write_file(path, &png.as_bytes())?;
-            println!("`{chunk_type}` message removed");
+    /// 默认按文件中的顺序输出所有匹配的块，存在多个时每个块前输出序号并以空行分隔
+    fn decode(&self, args: &args::DecodeArgs) -> Result<()> {
+        let chunk_type = &args.chunk_type;
+        let png = self.png_from_file_with(&args.path, !args.no_validate_type)?;
+        let mut chunks = png.chunks_by_type(chunk_type);
+        // 由 --split 拆分的块先拼接为一条信息
+        let joined = join_fragments(&chunks)?;
+        if let Some(joined) = &joined {
+            chunks = vec![joined];
+        }
+        if args.paranoid && chunks.len() > 1 {
+            return Err(Error::from(format!(
+                "found {} `{chunk_type}` chunks, refusing to pick one in paranoid mode",
+                chunks.len()
+            )));
+        }
+        if args.first || args.output.is_some() || args.raw {
+            chunks.truncate(1);
+        }
+
+        if chunks.is_empty() {
+            // raw 模式下标准输出只允许出现原始数据
+            if args.raw && !self.quiet {
+                eprintln!("{}", not_found_message(chunk_type));
+            } else {
+                self.status(not_found_message(chunk_type));
+            }
+            return Err(NotFound.into());
+        }
+        if let (Some(output), Some(chunk)) = (&args.output, chunks.first()) {
+            return self.write_file(output, &self.open_message(chunk, args)?);
+        }
+
+        let mut stdout = std::io::stdout().lock();
+        let labelled = chunks.len() > 1;
+        for (index, chunk) in chunks.iter().enumerate() {
+            if labelled {
+                if index > 0 {
+                    writeln!(stdout)?;
+                }
+                writeln!(stdout, "[{index}]")?;
+            }
+            match args.encoding {
+                args::DataEncoding::Utf8 => {
+                    let message = self.open_message(chunk, args)?;
+                    write_message(&mut stdout, &message, args.raw)?;
+                }
+                args::DataEncoding::Hex => writeln!(stdout, "{}", chunk.data_as_hex())?,
+                args::DataEncoding::Base64 => writeln!(stdout, "{}", chunk.data_as_base64())?,
+            }
         }
+        stdout.flush()?;
         Ok(())
     }
 
-    fn print(&self, path: &PathBuf) -> Result<()> {
-        let png = self.png_from_file(path)?;
+    /// 按 encode 的逆序还原信息：先解密，再解压
+    fn open_message(&self, chunk: &Chunk, args: &args::DecodeArgs) -> Result<Vec<u8>> {
+        match &args.password {
+            Some(password) => {
+                unpack_message(&crypto::decrypt(chunk.data(), password)?, args.decompress)
+            }
+            None => unpack_message(chunk.data(), args.decompress),
+        }
+    }
+
+    fn remove(
+        &self,
+        path: &PathBuf,
+        chunk_type: &str,
+        emit_report: Option<&PathBuf>,
+        no_validate_type: bool,
+        all: bool,
+        no_backup: bool,
+    ) -> Result<()> {
+        let (mut png, before) = self.png_with_snapshot(emit_report, path, !no_validate_type)?;
+        let removed = if all {
+            png.remove_all_chunks_by_type(chunk_type)
+        } else {
+            png.remove_first_chunk_by_type(chunk_type)
+                .into_iter()
+                .collect()
+        };
+        if !removed.is_empty() {
+            if !no_backup {
+                self.backup(path)?;
+            }
+            self.write_png(path, &png)?;
+            for chunk in &removed {
+                self.status_for(path, removed_message(chunk));
+            }
+        }
+        if all {
+            self.status_for(
+                path,
+                format!("`{chunk_type}` removed {} message(s)", removed.len()),
+            );
+        }
+        if let (Some(report), Some(before)) = (emit_report, before) {
+            Report::new(path, &before, path, &png.as_bytes())
+                .removed(&removed)
+                .write(report)?;
+        }
+        // `--all` 没有匹配的块时不视为错误
+        if removed.is_empty() && !all {
+            return self.not_found(chunk_type);
+        }
+        Ok(())
+    }
+
+    fn print(&self, path: &PathBuf, json: bool, lenient: bool) -> Result<()> {
+        let png = if lenient {
+            let (png, errors) = self.png_for_reading_lenient(path)?;
+            for error in errors {
+                eprintln!("warning: {error}");
+            }
+            png
+        } else {
+            self.png_for_reading(path)?
+        };
+        if json {
+            println!("{}", serde_json::to_string_pretty(&chunk_infos(&png))?);
+            return Ok(());
+        }
+
         for chunk in png.chunks() {
             if let Ok(data) = chunk.data_as_string() {
                 if !data.is_empty() {
@@ -108,17 +640,2015 @@ impl Commands {
         Ok(())
     }
 
-    fn check(&self, path: &PathBuf) -> Result<()> {
+    fn list(
+        &self,
+        path: &PathBuf,
+        preview: Option<usize>,
+        format: args::OutputFormat,
+    ) -> Result<()> {
+        let png = self.png_for_reading(path)?;
+        if format == args::OutputFormat::Json {
+            println!("{}", serde_json::to_string_pretty(&chunk_infos(&png))?);
+            return Ok(());
+        }
+
+        print!(
+            "{:>10}  {:<4}  {:>10}  {:<10}  ",
+            "OFFSET", "TYPE", "LENGTH", "CRC"
+        );
+        match preview {
+            Some(_) => println!("{:<38}  PREVIEW", "FLAGS"),
+            None => println!("FLAGS"),
+        }
+
+        for (index, chunk) in png.chunks().iter().enumerate() {
+            let range = png.byte_range_of(index).unwrap_or_default();
+            let chunk_type = chunk.chunk_type();
+            let flags = [
+                if chunk_type.is_critical() {
+                    "critical"
+                } else {
+                    "ancillary"
+                },
+                if chunk_type.is_public() {
+                    "public"
+                } else {
+                    "private"
+                },
+                if chunk_type.is_safe_to_copy() {
+                    "safe-to-copy"
+                } else {
+                    "unsafe-to-copy"
+                },
+            ];
+            let flags = flags.join(",");
+            print!(
+                "{:>10}  {:<4}  {:>10}  {:#010x}  ",
+                range.start,
+                chunk_type,
+                chunk.length(),
+                chunk.crc(),
+            );
+            match preview {
+                Some(n) => println!("{flags:<38}  {}", preview_bytes(chunk.data(), n)),
+                None => println!("{flags}"),
+            }
+        }
+
+        println!("{} chunks, {} bytes", png.chunks().len(), png.byte_len());
+        Ok(())
+    }
+
+    fn dupes(&self, path: &PathBuf) -> Result<()> {
         let png = self.png_from_file(path)?;
-        for chunk in png.chunks() {
-            if let Ok(data) = chunk.data_as_string() {
-                if !data.is_empty() {
-                    println!("include secret message");
-                    return Ok(());
+        for (chunk_type, count) in png.chunk_type_counts() {
+            if count > 1 {
+                println!("{chunk_type}: {count}");
+            }
+        }
+        Ok(())
+    }
+
+    /// 按类型排序输出每种块出现的次数，指定类型时只输出该类型的数量
+    fn count(&self, path: &PathBuf, chunk_type: Option<&str>) -> Result<()> {
+        let png = self.png_for_reading(path)?;
+        let Some(chunk_type) = chunk_type else {
+            for (chunk_type, count) in png.chunk_type_counts() {
+                println!("{chunk_type}: {count}");
+            }
+            return Ok(());
+        };
+
+        let count = png.chunk_count_by_type(chunk_type);
+        println!("{count}");
+        if count == 0 {
+            return Err(Error::from(format!("no `{chunk_type}` chunks found")));
+        }
+        Ok(())
+    }
+
+    fn extract_trailing_png(&self, path: &PathBuf, output: &PathBuf) -> Result<()> {
+        let data = self.read_file(path)?;
+        let (_, consumed) = Png::from_prefix(&data)?;
+        let trailing = &data[consumed..];
+        if trailing.is_empty() {
+            return Err(Error::from("no trailing PNG found after IEND"));
+        }
+
+        // 校验拼接在后面的数据是一个完整的 PNG
+        let (_, inner_len) = Png::from_prefix(trailing)?;
+        self.write_file(output, &trailing[..inner_len])?;
+        self.status_for(
+            output,
+            format!("extracted {inner_len} bytes to {}", output.display()),
+        );
+        Ok(())
+    }
+
+    /// 只读取文件的前 8 个字节，判断是否为 PNG 签名
+    fn has_png_signature(&self, path: &PathBuf) -> Result<bool> {
+        let mut header = Vec::new();
+        let _ = self
+            .open_file(path)?
+            .take(Png::STANDARD_HEADER.len() as u64)
+            .read_to_end(&mut header)?;
+        Ok(Png::has_valid_signature(&header))
+    }
+
+    fn check(
+        &self,
+        path: &PathBuf,
+        signature_only: bool,
+        verify_crc: bool,
+        lenient: bool,
+        chunk_type: Option<&str>,
+    ) -> Result<()> {
+        if signature_only {
+            if !self.has_png_signature(path)? {
+                return Err(Error::from("not a PNG file"));
+            }
+            println!("png file");
+            return Ok(());
+        }
+
+        let is_message = |chunk: &Chunk| match chunk_type {
+            Some(chunk_type) => chunk.chunk_type() == chunk_type && !chunk.data().is_empty(),
+            None => looks_like_message(chunk),
+        };
+        let (types, errors) = if verify_crc || lenient {
+            let (png, errors) = self.png_for_reading_lenient(path)?;
+            let mut types: Vec<ChunkType> = Vec::new();
+            for chunk in png.iter_chunks().filter(|chunk| is_message(chunk)) {
+                if !types.contains(chunk.chunk_type()) {
+                    types.push(*chunk.chunk_type());
+                }
+            }
+            (types, errors)
+        } else {
+            // 指定了类型时找到第一个信息后就停止读取，不解析剩余的块
+            (
+                self.matching_types(path, is_message, chunk_type.is_some())?,
+                Vec::new(),
+            )
+        };
+        let has_message = !types.is_empty();
+        self.status(check_message(&types));
+
+        for error in &errors {
+            println!("bad CRC: {error}");
+        }
+        if verify_crc && !errors.is_empty() {
+            return Err(Error::from(format!(
+                "{} chunks with CRC errors",
+                errors.len()
+            )));
+        }
+        if !has_message {
+            return Err(NotFound.into());
+        }
+        Ok(())
+    }
+
+    /// 宽松解析后输出所有结构问题，crc 错误也作为问题报告
+    fn validate(&self, path: &PathBuf) -> Result<()> {
+        let (png, _) = self.png_from_file_lenient(path)?;
+        let issues = png.validate();
+        for issue in &issues {
+            println!("{issue}");
+        }
+        let errors = issues
+            .iter()
+            .filter(|issue| issue.severity == Severity::Error)
+            .count();
+        if errors > 0 {
+            return Err(Error::from(format!("{errors} structural error(s) found")));
+        }
+        if issues.is_empty() {
+            println!("no problems found");
+        }
+        Ok(())
+    }
+
+    fn info(&self, path: &PathBuf) -> Result<()> {
+        let header = self.png_from_file(path)?.header()?;
+        println!("width: {}", header.width);
+        println!("height: {}", header.height);
+        println!("bit depth: {}", header.bit_depth);
+        println!("color type: {}", color_type_name(header.color_type));
+        println!("compression method: {}", header.compression);
+        println!("filter method: {}", header.filter);
+        println!("interlace method: {}", header.interlace);
+        Ok(())
+    }
+
+    fn list_text(&self, path: &PathBuf, group: bool) -> Result<()> {
+        let png = self.png_from_file(path)?;
+        let entries = png.text_chunks();
+        if group {
+            for (keyword, values) in group_by_keyword(entries) {
+                println!("{keyword}");
+                for value in values {
+                    println!("    {value}");
                 }
             }
+        } else {
+            for (keyword, value) in entries {
+                println!("{keyword}: {value}");
+            }
+        }
+        Ok(())
+    }
+
+    /// 删除辅助块（默认全部删除），关键块始终保留
+    fn strip(
+        &self,
+        path: &PathBuf,
+        unsafe_only: bool,
+        emit_report: Option<&PathBuf>,
+        output: Option<&PathBuf>,
+    ) -> Result<()> {
+        let (mut png, before) = self.png_with_snapshot(emit_report, path, true)?;
+        let removed = png.strip_ancillary(unsafe_only);
+        let output = output.unwrap_or(path);
+        if !removed.is_empty() || output != path {
+            self.write_png(output, &png)?;
+        }
+        let saved: usize = removed.iter().map(Chunk::chunk_length).sum();
+        self.status_for(
+            output,
+            format!("{} chunks removed, {saved} bytes saved", removed.len()),
+        );
+        if let (Some(report), Some(before)) = (emit_report, before) {
+            Report::new(path, &before, output, &png.as_bytes())
+                .removed(&removed)
+                .write(report)?;
+        }
+        Ok(())
+    }
+
+    /// 写入 tEXt 关键字和文本，相同关键字的块会被替换
+    fn set_text(
+        &self,
+        path: &PathBuf,
+        keyword: &str,
+        value: &str,
+        output: Option<&PathBuf>,
+    ) -> Result<()> {
+        let mut png = self.png_from_file(path)?;
+        png.set_text(keyword, value)?;
+        self.write_png(output.unwrap_or(path), &png)
+    }
+
+    /// 将同一类型的多个块合并为一个，减少块的数量
+    fn compact(&self, path: &PathBuf, chunk_type: &str, output: Option<&PathBuf>) -> Result<()> {
+        let mut png = self.png_from_file(path)?;
+        let compacted = png.compact(chunk_type);
+        if compacted == 0 {
+            return self.not_found(chunk_type);
+        }
+
+        let output = output.unwrap_or(path);
+        self.write_png(output, &png)?;
+        self.status_for(
+            output,
+            format!("{compacted} `{chunk_type}` chunks compacted into one"),
+        );
+        Ok(())
+    }
+
+    /// 跳过 crc 校验解析文件，重新计算所有块的 crc 后写回
+    fn repair_crc(&self, path: &PathBuf, output: Option<&PathBuf>) -> Result<()> {
+        let (mut png, _) = self.png_from_file_lenient(path)?;
+        let corrected = png.repair_crcs();
+        let output = output.unwrap_or(path);
+        self.write_png(output, &png)?;
+        self.status_for(output, format!("{corrected} CRCs corrected"));
+        Ok(())
+    }
+
+    /// 导出 PNG 结构的 JSON 清单
+    fn manifest(&self, path: &PathBuf, output: Option<&PathBuf>) -> Result<()> {
+        let png = self.png_from_file(path)?;
+        let json = serde_json::to_string_pretty(&Manifest::from_png(&png))?;
+        match output {
+            Some(output) => self.write_file(output, json.as_bytes()),
+            None => {
+                println!("{json}");
+                Ok(())
+            }
+        }
+    }
+
+    /// 按 JSON 清单重新构造 PNG
+    fn rebuild(&self, manifest: &PathBuf, output: &PathBuf) -> Result<()> {
+        let manifest: Manifest = serde_json::from_slice(&self.read_file(manifest)?)?;
+        self.write_png(output, &manifest.to_png()?)
+    }
+
+    /// 按熵从高到低输出每个块的数据熵，高熵的辅助块很可能藏有加密或压缩过的数据
+    fn stats(&self, path: &PathBuf, flag_threshold: f64) -> Result<()> {
+        let png = self.png_from_file(path)?;
+        let mut stats: Vec<(&Chunk, f64)> = png
+            .chunks()
+            .iter()
+            .map(|chunk| (chunk, shannon_entropy(chunk.data())))
+            .collect();
+        stats.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+        println!("{:<4}  {:>10}  {:>7}", "TYPE", "LENGTH", "ENTROPY");
+        for (chunk, entropy) in stats {
+            let suspicious = chunk.chunk_type().is_ancillary() && entropy > flag_threshold;
+            println!(
+                "{:<4}  {:>10}  {:>7.3}{}",
+                chunk.chunk_type(),
+                chunk.length(),
+                entropy,
+                if suspicious { "  suspicious" } else { "" }
+            );
+        }
+        Ok(())
+    }
+
+    /// 比较两个文件的块结构，只输出不同的块
+    fn diff(&self, a: &PathBuf, b: &PathBuf) -> Result<()> {
+        let (a, b) = (self.png_from_file(a)?, self.png_from_file(b)?);
+        let mut identical = 0;
+        for diff in diff_chunks(&a, &b) {
+            match diff {
+                ChunkDiff::Same => identical += 1,
+                diff => println!("{diff}"),
+            }
+        }
+        println!("{identical} identical");
+        Ok(())
+    }
+
+    /// 将每个辅助块的原始数据写入 `<out_dir>/<type>_<index>.bin`，index 为块在文件中的序号
+    fn extract_all(&self, path: &PathBuf, out_dir: &PathBuf) -> Result<()> {
+        let png = self.png_from_file(path)?;
+        std::fs::create_dir_all(out_dir)?;
+        let mut written = 0;
+        for (index, chunk) in png.iter_chunks().enumerate() {
+            if chunk.chunk_type().is_critical() {
+                continue;
+            }
+            let output = out_dir.join(format!("{}_{index}.bin", chunk.chunk_type()));
+            self.write_file(&output, chunk.data())?;
+            self.status(format!("{} ({} bytes)", output.display(), chunk.length()));
+            written += 1;
         }
-        println!("exculde secret message");
+        self.status(format!("{written} files written to {}", out_dir.display()));
         Ok(())
     }
 }
+
+/// 根据 `args::Args` 的命令定义生成 shell 补全脚本
+fn write_completions(shell: clap_complete::Shell, output: &mut impl Write) {
+    use clap::CommandFactory;
+
+    clap_complete::generate(shell, &mut args::Args::command(), "pngme", output);
+}
+
+/// `diff` 中按 (类型, 该类型的第几个块) 配对后的比较结果
+#[derive(Debug, PartialEq)]
+enum ChunkDiff<'a> {
+    Same,
+    Removed(&'a Chunk),
+    Added(&'a Chunk),
+    Changed(&'a Chunk, &'a Chunk),
+}
+
+impl std::fmt::Display for ChunkDiff<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ChunkDiff::Same => write!(f, "="),
+            ChunkDiff::Removed(chunk) => {
+                write!(f, "- {} ({} bytes)", chunk.chunk_type(), chunk.length())
+            }
+            ChunkDiff::Added(chunk) => {
+                write!(f, "+ {} ({} bytes)", chunk.chunk_type(), chunk.length())
+            }
+            ChunkDiff::Changed(a, b) => write!(
+                f,
+                "~ {} ({} -> {} bytes, crc {:#010x} -> {:#010x})",
+                a.chunk_type(),
+                a.length(),
+                b.length(),
+                a.stored_crc(),
+                b.stored_crc()
+            ),
+        }
+    }
+}
+
+/// 先按 a 的顺序输出相同、修改和删除的块，再按 b 的顺序输出新增的块
+fn diff_chunks<'a>(a: &'a Png, b: &'a Png) -> Vec<ChunkDiff<'a>> {
+    let keyed = |png: &'a Png| {
+        let mut seen: HashMap<ChunkType, usize> = HashMap::new();
+        png.iter_chunks()
+            .map(|chunk| {
+                let occurrence = seen.entry(*chunk.chunk_type()).or_default();
+                *occurrence += 1;
+                ((*chunk.chunk_type(), *occurrence), chunk)
+            })
+            .collect::<Vec<_>>()
+    };
+    let (a, b) = (keyed(a), keyed(b));
+    let b_map: HashMap<_, _> = b.iter().copied().collect();
+    let a_keys: HashSet<_> = a.iter().map(|(key, _)| *key).collect();
+
+    let mut diffs: Vec<ChunkDiff> = a
+        .iter()
+        .map(|(key, chunk)| match b_map.get(key) {
+            None => ChunkDiff::Removed(chunk),
+            Some(other)
+                if other.data() != chunk.data() || other.stored_crc() != chunk.stored_crc() =>
+            {
+                ChunkDiff::Changed(chunk, other)
+            }
+            Some(_) => ChunkDiff::Same,
+        })
+        .collect();
+    diffs.extend(
+        b.iter()
+            .filter(|(key, _)| !a_keys.contains(key))
+            .map(|(_, chunk)| ChunkDiff::Added(chunk)),
+    );
+    diffs
+}
+
+/// 按字节分布计算香农熵，单位为比特/字节，取值范围 0..=8
+fn shannon_entropy(data: &[u8]) -> f64 {
+    if data.is_empty() {
+        return 0.0;
+    }
+
+    let mut histogram = [0usize; 256];
+    for &byte in data {
+        histogram[byte as usize] += 1;
+    }
+
+    let len = data.len() as f64;
+    histogram
+        .iter()
+        .filter(|&&count| count > 0)
+        .map(|&count| {
+            let p = count as f64 / len;
+            p * (1.0 / p).log2()
+        })
+        .sum()
+}
+
+/// 按关键字第一次出现的顺序分组
+fn group_by_keyword(entries: Vec<(String, String)>) -> Vec<(String, Vec<String>)> {
+    let mut groups: Vec<(String, Vec<String>)> = Vec::new();
+    for (keyword, value) in entries {
+        match groups.iter_mut().find(|(k, _)| *k == keyword) {
+            Some((_, values)) => values.push(value),
+            None => groups.push((keyword, vec![value])),
+        }
+    }
+    groups
+}
+
+/// 私有、辅助、非标准的块中是否有非空的文本，标准的 tEXt 等块不算作隐藏的信息
+fn looks_like_message(chunk: &Chunk) -> bool {
+    let chunk_type = chunk.chunk_type();
+    chunk_type.is_ancillary()
+        && chunk_type.is_private()
+        && !chunk_type.is_standard()
+        && chunk.data_as_string().is_ok_and(|data| !data.is_empty())
+}
+
+/// 找不到指定类型的块时的提示，类型疑似拼写错误时给出建议
+fn not_found_message(chunk_type: &str) -> String {
+    match ChunkType::suggest(chunk_type) {
+        Some(known) => format!("`{chunk_type}` message not exists; did you mean `{known}`?"),
+        None => format!("`{chunk_type}` message not exists"),
+    }
+}
+
+/// encode 写入后的摘要，例如 `wrote 1 ruSt chunk (26 bytes) to out.png: 1000 -> 1026 bytes`
+fn encode_summary(
+    chunk_type: &str,
+    chunks: &[Chunk],
+    original_size: usize,
+    new_size: usize,
+    output: &std::path::Path,
+) -> String {
+    let added: usize = chunks.iter().map(Chunk::chunk_length).sum();
+    format!(
+        "wrote {} {chunk_type} chunk{} ({added} bytes) to {}: {original_size} -> {new_size} bytes",
+        chunks.len(),
+        if chunks.len() == 1 { "" } else { "s" },
+        output.display()
+    )
+}
+
+/// check 的输出：`secret message found: ruSt, abCd` 或 `no secret message found`
+fn check_message(types: &[ChunkType]) -> String {
+    if types.is_empty() {
+        return "no secret message found".to_string();
+    }
+    let types: Vec<String> = types.iter().map(ChunkType::to_string).collect();
+    format!("secret message found: {}", types.join(", "))
+}
+
+/// remove 输出的被删除块的摘要，例如 `removed ruSt (42 bytes, crc 0x1a2b3c4d)`
+fn removed_message(chunk: &Chunk) -> String {
+    format!(
+        "removed {} ({} bytes, crc {:#010x})",
+        chunk.chunk_type(),
+        chunk.length(),
+        chunk.stored_crc()
+    )
+}
+
+/// IHDR 中颜色类型对应的名称，未知的类型直接输出数值
+fn color_type_name(color_type: u8) -> String {
+    match color_type {
+        0 => "Grayscale".to_string(),
+        2 => "RGB".to_string(),
+        3 => "Indexed".to_string(),
+        4 => "Grayscale+Alpha".to_string(),
+        6 => "RGBA".to_string(),
+        other => other.to_string(),
+    }
+}
+
+/// `print --json` 和 `list --format json` 输出的块元数据，不包含块数据
+#[derive(Debug, Serialize)]
+struct ChunkInfo {
+    #[serde(rename = "type")]
+    chunk_type: String,
+    length: usize,
+    /// 根据数据重新计算的 crc，可与文件中保存的值对比
+    crc: u32,
+    crc_valid: bool,
+    offset: usize,
+    critical: bool,
+    public: bool,
+    safe_to_copy: bool,
+}
+
+fn chunk_infos(png: &Png) -> Vec<ChunkInfo> {
+    png.chunks()
+        .iter()
+        .enumerate()
+        .map(|(index, chunk)| {
+            let chunk_type = chunk.chunk_type();
+            ChunkInfo {
+                chunk_type: chunk_type_label(chunk_type),
+                length: chunk.length(),
+                crc: chunk.crc(),
+                crc_valid: chunk.is_crc_valid(),
+                offset: png.byte_range_of(index).unwrap_or_default().start,
+                critical: chunk_type.is_critical(),
+                public: chunk_type.is_public(),
+                safe_to_copy: chunk_type.is_safe_to_copy(),
+            }
+        })
+        .collect()
+}
+
+/// 块类型的 ASCII 表示，不可打印的字节转义为 `\xNN`
+fn chunk_type_label(chunk_type: &ChunkType) -> String {
+    chunk_type
+        .bytes()
+        .iter()
+        .flat_map(|&b| std::ascii::escape_default(b))
+        .map(char::from)
+        .collect()
+}
+
+/// 解析命令行中的块类型，长度或字符不对时给出比 `ChunkType::from_str` 更明确的错误，
+/// 第三个字母（保留位）必须大写
+fn parse_chunk_type(chunk_type: &str) -> Result<ChunkType> {
+    if chunk_type.len() != 4 || !chunk_type.bytes().all(|b| b.is_ascii_alphabetic()) {
+        return Err(Error::from(format!(
+            "chunk type must be exactly 4 ASCII letters, got '{chunk_type}' ({} bytes)",
+            chunk_type.len()
+        )));
+    }
+    let parsed: ChunkType = chunk_type.parse()?;
+    if !parsed.is_reserved_bit_valid() {
+        let mut message =
+            format!("the third letter of chunk type '{chunk_type}' must be uppercase");
+        if let Some(fixed) = ChunkType::suggest_private(chunk_type) {
+            message.push_str(&format!("; did you mean '{fixed}'?"));
+        }
+        return Err(Error::from(message));
+    }
+    Ok(parsed)
+}
+
+fn backup_path(path: &std::path::Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".bak");
+    PathBuf::from(name)
+}
+
+/// 路径 `-` 表示标准输入或标准输出
+fn is_stdio(path: &std::path::Path) -> bool {
+    path.as_os_str() == "-"
+}
+
+/// 默认的块类型：私有、辅助、可安全复制
+const DEFAULT_CHUNK_TYPE: &str = "ruSt";
+
+/// 询问一行输入，直接回车时使用 default
+fn prompt(
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+    question: &str,
+    default: Option<&str>,
+) -> Result<String> {
+    match default {
+        Some(default) => write!(output, "{question} [{default}]: ")?,
+        None => write!(output, "{question}: ")?,
+    }
+    output.flush()?;
+
+    let mut answer = String::new();
+    if input.read_line(&mut answer)? == 0 {
+        return Err(Error::from("unexpected end of input"));
+    }
+    let answer = answer.trim_end_matches(['\r', '\n']);
+    match (answer.is_empty(), default) {
+        (true, Some(default)) => Ok(default.to_string()),
+        (true, None) => Err(Error::from(format!("{question} cannot be empty"))),
+        (false, _) => Ok(answer.to_string()),
+    }
+}
+
+/// `encode --interactive` 依次询问块类型、信息和输出文件，返回填好这些参数的 EncodeArgs
+fn prompt_encode_args(
+    args: &args::EncodeArgs,
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+) -> Result<args::EncodeArgs> {
+    let default_type = args.chunk_type.as_deref().unwrap_or(DEFAULT_CHUNK_TYPE);
+    let chunk_type = prompt(input, output, "chunk type", Some(default_type))?;
+    parse_chunk_type(&chunk_type)?;
+    let message = prompt(input, output, "message", None)?;
+    let default_output = args.output.as_ref().unwrap_or(&args.path);
+    let path = prompt(
+        input,
+        output,
+        "output file",
+        Some(&default_output.to_string_lossy()),
+    )?;
+
+    Ok(args::EncodeArgs {
+        chunk_type: Some(chunk_type),
+        message: args::MessageArgs {
+            message: Some(message),
+            message_file: None,
+            stdin: false,
+            interactive: false,
+        },
+        output: Some(PathBuf::from(path)),
+        ..args.clone()
+    })
+}
+
+/// 询问是否覆盖已存在的块，非交互环境下不询问，直接覆盖
+fn confirm_overwrite(
+    interactive: bool,
+    existing: &Chunk,
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+) -> Result<bool> {
+    if !interactive {
+        return Ok(true);
+    }
+
+    write!(
+        output,
+        "chunk '{}' exists ({} bytes), overwrite? [y/N] ",
+        existing.chunk_type(),
+        existing.length()
+    )?;
+    output.flush()?;
+
+    let mut answer = String::new();
+    let _ = input.read_line(&mut answer)?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
+
+/// 在单独的线程中打开并读取全部数据，超过 timeout 仍未读完时报错。
+/// 超时后读取线程会被留下，进程退出时一并结束
+fn read_with_timeout<R, F>(timeout: Option<Duration>, open: F) -> Result<Vec<u8>>
+where
+    R: Read,
+    F: FnOnce() -> std::io::Result<R> + Send + 'static,
+{
+    let read = move || -> std::io::Result<Vec<u8>> {
+        let mut buf = Vec::new();
+        let _ = open()?.read_to_end(&mut buf)?;
+        Ok(buf)
+    };
+    let Some(timeout) = timeout else {
+        return Ok(read()?);
+    };
+
+    let (sender, receiver) = mpsc::channel();
+    std::thread::spawn(move || {
+        let _ = sender.send(read());
+    });
+    match receiver.recv_timeout(timeout) {
+        Ok(result) => Ok(result?),
+        Err(_) => Err(Error::from(format!(
+            "timed out after {}s waiting for message input",
+            timeout.as_secs_f64()
+        ))),
+    }
+}
+
+/// 压缩后的信息以此开头，decode 据此判断是否需要解压
+const COMPRESSED_MAGIC: &[u8; 4] = b"PZ01";
+
+/// 指定了压缩级别时使用 zlib 压缩信息并加上 `PZ01` 前缀，否则原样返回
+fn pack_message(data: Vec<u8>, compression: Option<Compression>) -> Result<Vec<u8>> {
+    let Some(compression) = compression else {
+        return Ok(data);
+    };
+
+    let mut encoder = ZlibEncoder::new(COMPRESSED_MAGIC.to_vec(), compression);
+    encoder.write_all(&data)?;
+    Ok(encoder.finish()?)
+}
+
+/// 以 `PZ01` 开头且之后是完整的 zlib 数据时解压，其余数据（包括恰好以 `PZ01`
+/// 开头的普通信息）原样返回。require_compressed 为 true 时，无法解压的数据会报错
+fn unpack_message(data: &[u8], require_compressed: bool) -> Result<Vec<u8>> {
+    if let Some(compressed) = data.strip_prefix(COMPRESSED_MAGIC) {
+        let mut message = Vec::new();
+        match ZlibDecoder::new(compressed).read_to_end(&mut message) {
+            Ok(_) => return Ok(message),
+            Err(e) if require_compressed => return Err(e.into()),
+            Err(_) => {}
+        }
+    }
+    if require_compressed {
+        return Err(Error::from(
+            "message is not compressed (missing `PZ01` prefix)",
+        ));
+    }
+    Ok(data.to_vec())
+}
+
+const SPLIT_MAGIC: &[u8; 4] = b"PS01";
+/// 拆分块的序号头：`PS01` + 序号（u32）+ 总数（u32），均为大端序
+const SPLIT_HEADER_LEN: usize = SPLIT_MAGIC.len() + 8;
+
+/// 将数据按 size 字节拆分，每段前加上序号头
+fn split_message(data: &[u8], size: usize) -> Result<Vec<Vec<u8>>> {
+    if size == 0 {
+        return Err(Error::from("`--split` size must be greater than 0"));
+    }
+    let parts: Vec<&[u8]> = if data.is_empty() {
+        vec![data]
+    } else {
+        data.chunks(size).collect()
+    };
+    let total = u32::try_from(parts.len())?;
+    Ok((0..total)
+        .zip(parts)
+        .map(|(index, part)| {
+            let mut fragment = SPLIT_MAGIC.to_vec();
+            fragment.extend(index.to_be_bytes());
+            fragment.extend(total.to_be_bytes());
+            fragment.extend(part);
+            fragment
+        })
+        .collect())
+}
+
+/// 所有块都带有一致的序号头时，按序号拼接所有块并返回拼接后的块。
+/// 序号头必须满足 total >= 1、index < total，所有块的 total 相同且等于块的数量、
+/// 序号不重复；否则视为恰好以 `PS01` 开头的普通信息，返回 None
+fn join_fragments(chunks: &[&Chunk]) -> Result<Option<Chunk>> {
+    let Some(first) = chunks.first() else {
+        return Ok(None);
+    };
+
+    let mut fragments = Vec::with_capacity(chunks.len());
+    for chunk in chunks {
+        let data = chunk.data();
+        if data.len() < SPLIT_HEADER_LEN || !data.starts_with(SPLIT_MAGIC) {
+            return Ok(None);
+        }
+        let index = u32::from_be_bytes(data[4..8].try_into()?);
+        let total = u32::from_be_bytes(data[8..12].try_into()?);
+        if total as usize != chunks.len() || index >= total {
+            return Ok(None);
+        }
+        fragments.push((index, &data[SPLIT_HEADER_LEN..]));
+    }
+    fragments.sort_by_key(|&(index, _)| index);
+    if !fragments
+        .iter()
+        .enumerate()
+        .all(|(i, &(index, _))| index as usize == i)
+    {
+        return Ok(None);
+    }
+
+    let data = fragments
+        .into_iter()
+        .flat_map(|(_, part)| part)
+        .copied()
+        .collect();
+    Ok(Some(Chunk::new(*first.chunk_type(), data)))
+}
+
+/// 输出块中隐藏的信息，raw 模式下原样写入数据，不追加换行
+fn write_message(out: &mut impl Write, message: &[u8], raw: bool) -> Result<()> {
+    if raw {
+        out.write_all(message)?;
+    } else if let Ok(text) = std::str::from_utf8(message) {
+        writeln!(out, "{text}")?;
+    } else {
+        write!(out, "{}", hex_dump(message))?;
+    }
+    Ok(())
+}
+
+/// 数据前 n 个字节的十六进制和 ASCII 预览，超出部分以 `…` 表示
+fn preview_bytes(data: &[u8], n: usize) -> String {
+    let shown = &data[..data.len().min(n)];
+    let hex: Vec<String> = shown.iter().map(|b| format!("{b:02x}")).collect();
+    let ascii: String = shown
+        .iter()
+        .map(|&b| {
+            if b.is_ascii_graphic() || b == b' ' {
+                b as char
+            } else {
+                '.'
+            }
+        })
+        .collect();
+    let ellipsis = if data.len() > n { "…" } else { "" };
+    format!("{} |{ascii}|{ellipsis}", hex.join(" "))
+}
+
+/// 以每行 16 字节的格式输出数据的十六进制表示
+fn hex_dump(data: &[u8]) -> String {
+    let mut dump = String::new();
+    for (i, line) in data.chunks(16).enumerate() {
+        let hex: Vec<String> = line.iter().map(|b| format!("{b:02x}")).collect();
+        dump.push_str(&format!("{:08x}  {}\n", i * 16, hex.join(" ")));
+    }
+    dump
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn testing_png_file(name: &str, chunks: &[(&str, &str)]) -> PathBuf {
+        let mut bytes = vec![137, 80, 78, 71, 13, 10, 26, 10];
+        for (chunk_type, data) in chunks {
+            let chunk_type =
+                ChunkType::from_bytes_unchecked(chunk_type.as_bytes().try_into().unwrap());
+            bytes.extend(Chunk::new(chunk_type, data.as_bytes().to_vec()).as_bytes());
+        }
+
+        let path = std::env::temp_dir().join(format!("pngme-{}-{name}.png", std::process::id()));
+        std::fs::write(&path, bytes).unwrap();
+        path
+    }
+
+    fn encode_args(path: &std::path::Path, message: &str) -> args::EncodeArgs {
+        args::EncodeArgs {
+            path: path.to_path_buf(),
+            chunk_type: Some("ruSt".to_string()),
+            message: args::MessageArgs {
+                message: Some(message.to_string()),
+                message_file: None,
+                stdin: false,
+                interactive: false,
+            },
+            output: None,
+            replace_crc_policy: Default::default(),
+            yes: true,
+            force: false,
+            verify_with: None,
+            compress: false,
+            compress_level: 6,
+            read_timeout: None,
+            emit_report: None,
+            password: None,
+            before_idat: false,
+            at: None,
+            after: None,
+            before: None,
+            split: None,
+            no_backup: true,
+            strip_trailing: false,
+        }
+    }
+
+    fn decode_args(path: &std::path::Path) -> args::DecodeArgs {
+        args::DecodeArgs {
+            path: path.to_path_buf(),
+            chunk_type: "ruSt".to_string(),
+            paranoid: false,
+            first: false,
+            output: None,
+            raw: false,
+            decompress: false,
+            password: None,
+            no_validate_type: false,
+            encoding: args::DataEncoding::Utf8,
+        }
+    }
+
+    #[test]
+    fn test_decode_paranoid_single_match() {
+        let path = testing_png_file("paranoid-single", &[("ruSt", "first")]);
+        let args = args::DecodeArgs {
+            paranoid: true,
+            ..decode_args(&path)
+        };
+        let result = Commands::default().decode(&args);
+        std::fs::remove_file(&path).unwrap();
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_decode_paranoid_ambiguous_match() {
+        let path = testing_png_file(
+            "paranoid-ambiguous",
+            &[("ruSt", "first"), ("ruSt", "second")],
+        );
+        let paranoid = args::DecodeArgs {
+            paranoid: true,
+            ..decode_args(&path)
+        };
+        let ambiguous = Commands::default().decode(&paranoid);
+        let all = Commands::default().decode(&decode_args(&path));
+        std::fs::remove_file(&path).unwrap();
+        assert!(ambiguous.is_err());
+        assert!(all.is_ok());
+    }
+
+    #[test]
+    fn test_decode_output_file() {
+        let path = testing_png_file(
+            "decode-output",
+            &[("ruSt", "hidden message"), ("ruSt", "second")],
+        );
+        let output = path.with_extension("bin");
+        let args = args::DecodeArgs {
+            output: Some(output.clone()),
+            ..decode_args(&path)
+        };
+        let result = Commands::default().decode(&args);
+        let written = std::fs::read(&output);
+        std::fs::remove_file(&path).unwrap();
+        let _ = std::fs::remove_file(&output);
+        assert!(result.is_ok());
+        assert_eq!(written.unwrap(), b"hidden message");
+    }
+
+    #[test]
+    fn test_check_signature_only() {
+        let png = testing_png_file("signature-png", &[("ruSt", "message")]);
+        let text = std::env::temp_dir().join(format!("pngme-{}-signature.txt", std::process::id()));
+        std::fs::write(&text, "just some text").unwrap();
+
+        let commands = Commands::default();
+        let png_result = commands.check(&png, true, false, false, None);
+        let text_result = commands.check(&text, true, false, false, None);
+        std::fs::remove_file(&png).unwrap();
+        std::fs::remove_file(&text).unwrap();
+
+        assert!(png_result.is_ok());
+        assert!(text_result.is_err());
+    }
+
+    #[test]
+    fn test_remove_all() {
+        let path = testing_png_file(
+            "remove-all",
+            &[("ruSt", "first"), ("ruSt", "second"), ("IEND", "")],
+        );
+        let chunk_type = "ruSt".to_string();
+        let first = Commands::default().remove(&path, &chunk_type, None, false, false, true);
+        let after_first = Commands::default().png_from_file(&path).unwrap();
+        let all = Commands::default().remove(&path, &chunk_type, None, false, true, true);
+        let missing = Commands::default().remove(&path, &chunk_type, None, false, true, true);
+        let png = Commands::default().png_from_file(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(first.is_ok());
+        assert_eq!(after_first.chunk_by_type("ruSt").unwrap().data(), b"second");
+        assert!(all.is_ok());
+        assert!(missing.is_ok());
+        assert_eq!(png.unwrap().chunks().len(), 1);
+    }
+
+    #[test]
+    fn test_quiet_not_found() {
+        let path = testing_png_file("quiet-not-found", &[("ruSt", "message"), ("IEND", "")]);
+        let quiet = Commands::new(false, true, false);
+        let missing = quiet.remove(&path, "noPe", None, false, false, true);
+        let decode_missing = quiet.decode(&args::DecodeArgs {
+            chunk_type: "noPe".to_string(),
+            ..decode_args(&path)
+        });
+        let missing_all = quiet.remove(&path, "noPe", None, false, true, true);
+        let compact_missing = quiet.compact(&path, "noPe", None);
+        let removed = quiet.remove(&path, "ruSt", None, false, false, true);
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(missing.unwrap_err().is::<NotFound>());
+        assert!(decode_missing.unwrap_err().is::<NotFound>());
+        assert!(missing_all.is_ok());
+        assert!(compact_missing.unwrap_err().is::<NotFound>());
+        assert!(removed.is_ok());
+    }
+
+    #[test]
+    fn test_remove_no_validate_type() {
+        let path = testing_png_file("no-validate-type", &[("ru5t", "digit"), ("IEND", "")]);
+        let checked = Commands::default().remove(&path, "ru5t", None, false, false, true);
+        let unchecked = Commands::default().remove(&path, "ru5t", None, true, false, true);
+        let png = Commands::default().png_from_file(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert!(checked.is_err());
+        assert!(unchecked.is_ok());
+        assert_eq!(png.unwrap().chunks().len(), 1);
+    }
+
+    #[test]
+    fn test_check_not_found() {
+        let path = testing_png_file("check-not-found", &[("tEXt", "a\0b"), ("IEND", "")]);
+        let missing = Commands::default().check(&path, false, false, false, None);
+        let found = Commands::default().check(&path, false, false, false, Some("tEXt"));
+        std::fs::remove_file(&path).unwrap();
+        assert!(missing.unwrap_err().is::<NotFound>());
+        assert!(found.is_ok());
+    }
+
+    #[test]
+    fn test_check_verify_crc() {
+        let path = testing_png_file("verify-crc", &[("ruSt", "message"), ("IEND", "")]);
+        assert!(Commands::default()
+            .check(&path, false, true, false, None)
+            .is_ok());
+
+        // 破坏 ruSt 块的 crc
+        let mut bytes = std::fs::read(&path).unwrap();
+        bytes[8 + 12 + 7 - 1] ^= 0xff;
+        std::fs::write(&path, bytes).unwrap();
+        let verified = Commands::default().check(&path, false, true, false, None);
+        let lenient = Commands::default().check(&path, false, false, true, None);
+        let strict = Commands::default().check(&path, false, false, false, None);
+        std::fs::remove_file(&path).unwrap();
+        assert!(verified.is_err());
+        assert!(lenient.is_ok());
+        assert!(strict.is_err());
+    }
+
+    #[test]
+    fn test_extract_trailing_png() {
+        let outer = testing_png_file("trailing-outer", &[("IEND", "")]);
+        let inner = testing_png_file("trailing-inner", &[("ruSt", "inner"), ("IEND", "")]);
+        let mut bytes = std::fs::read(&outer).unwrap();
+        let inner_bytes = std::fs::read(&inner).unwrap();
+        bytes.extend(&inner_bytes);
+        std::fs::write(&outer, bytes).unwrap();
+
+        let output = outer.with_extension("inner.png");
+        let result = Commands::default().extract_trailing_png(&outer, &output);
+        let extracted = std::fs::read(&output);
+        std::fs::remove_file(&outer).unwrap();
+        std::fs::remove_file(&inner).unwrap();
+        let _ = std::fs::remove_file(&output);
+
+        assert!(result.is_ok());
+        let extracted = extracted.unwrap();
+        assert_eq!(extracted, inner_bytes);
+        assert!(Png::try_from(extracted.as_slice()).is_ok());
+    }
+
+    #[test]
+    fn test_verify_with() {
+        let path = testing_png_file("verify-with", &[("ruSt", "message")]);
+        let commands = Commands::default();
+        let passed = commands.verify_with("test -f", &path);
+        let failed = commands.verify_with("test -d", &path);
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(passed.is_ok());
+        assert!(failed.unwrap_err().to_string().contains("rejected"));
+    }
+
+    #[test]
+    fn test_confirm_overwrite_non_interactive() {
+        let existing = Chunk::new(ChunkType::from_str("ruSt").unwrap(), b"old".to_vec());
+        let mut input: &[u8] = b"n\n";
+        let mut output = Vec::new();
+        assert!(confirm_overwrite(false, &existing, &mut input, &mut output).unwrap());
+        assert!(output.is_empty());
+    }
+
+    #[test]
+    fn test_confirm_overwrite_interactive() {
+        let existing = Chunk::new(ChunkType::from_str("ruSt").unwrap(), b"old".to_vec());
+        let mut output = Vec::new();
+
+        let mut input: &[u8] = b"y\n";
+        assert!(confirm_overwrite(true, &existing, &mut input, &mut output).unwrap());
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "chunk 'ruSt' exists (3 bytes), overwrite? [y/N] "
+        );
+
+        let mut input: &[u8] = b"\n";
+        assert!(!confirm_overwrite(true, &existing, &mut input, &mut Vec::new()).unwrap());
+    }
+
+    #[test]
+    fn test_write_message_raw() {
+        let data = vec![0xff, 0x00, b'a', b'\n', 0x80];
+
+        let mut out = Vec::new();
+        write_message(&mut out, &data, true).unwrap();
+        assert_eq!(out, data);
+    }
+
+    #[test]
+    fn test_write_message_text() {
+        let mut out = Vec::new();
+        write_message(&mut out, b"hello", false).unwrap();
+        assert_eq!(out, b"hello\n");
+    }
+
+    #[test]
+    fn test_encode_compress() {
+        let path = testing_png_file("encode-compress", &[]);
+        let message = "pngme ".repeat(10 * 1024 / 6);
+        let args = args::EncodeArgs {
+            compress: true,
+            ..encode_args(&path, &message)
+        };
+        let result = Commands::default().encode(&args);
+        let png = Commands::default().png_from_file(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert!(result.is_ok());
+
+        let chunk = png.unwrap().chunk_by_type("ruSt").unwrap();
+        assert!(chunk.length() < message.len() / 10);
+        assert!(chunk.data().starts_with(b"PZ01"));
+        assert_eq!(
+            unpack_message(chunk.data(), true).unwrap(),
+            message.as_bytes()
+        );
+    }
+
+    #[test]
+    fn test_encode_compress_level() {
+        let message: String = (0..4096).map(|i| format!("line {} ", i % 97)).collect();
+        let stored_length = |level| {
+            let path = testing_png_file(&format!("compress-level-{level}"), &[]);
+            let args = args::EncodeArgs {
+                compress: true,
+                compress_level: level,
+                ..encode_args(&path, &message)
+            };
+            Commands::default().encode(&args).unwrap();
+            let png = Commands::default().png_from_file(&path).unwrap();
+            std::fs::remove_file(&path).unwrap();
+            png.chunk_by_type("ruSt").unwrap().length()
+        };
+        let (stored, fast, best) = (stored_length(0), stored_length(1), stored_length(9));
+        assert!(best <= fast, "level 9: {best} bytes, level 1: {fast} bytes");
+        assert!(fast < stored);
+
+        use clap::Parser;
+        let parse = |level: &str| {
+            args::Args::try_parse_from([
+                "pngme",
+                "encode",
+                "a.png",
+                "-c",
+                "ruSt",
+                "-m",
+                "hi",
+                "--compress",
+                "--compress-level",
+                level,
+            ])
+        };
+        assert!(parse("9").is_ok());
+        assert!(parse("10").is_err());
+    }
+
+    #[test]
+    fn test_parse_chunk_type() {
+        assert_eq!(parse_chunk_type("ruSt").unwrap(), "ruSt");
+        assert_eq!(
+            parse_chunk_type("ruS").unwrap_err().to_string(),
+            "chunk type must be exactly 4 ASCII letters, got 'ruS' (3 bytes)"
+        );
+        assert_eq!(
+            parse_chunk_type("ruStt").unwrap_err().to_string(),
+            "chunk type must be exactly 4 ASCII letters, got 'ruStt' (5 bytes)"
+        );
+        assert_eq!(
+            parse_chunk_type("ruСт").unwrap_err().to_string(),
+            "chunk type must be exactly 4 ASCII letters, got 'ruСт' (6 bytes)"
+        );
+        assert!(parse_chunk_type("ru5t").is_err());
+        assert_eq!(
+            parse_chunk_type("rust").unwrap_err().to_string(),
+            "the third letter of chunk type 'rust' must be uppercase; did you mean 'ruSt'?"
+        );
+        assert_eq!(
+            parse_chunk_type("RUst").unwrap_err().to_string(),
+            "the third letter of chunk type 'RUst' must be uppercase; did you mean 'ruSt'?"
+        );
+    }
+
+    #[test]
+    fn test_encode_checks_chunk_type_before_reading() {
+        let args = encode_args(std::path::Path::new("/nonexistent/pngme.png"), "message");
+        let args = args::EncodeArgs {
+            chunk_type: Some("toolong".to_string()),
+            ..args
+        };
+        let err = Commands::default().encode(&args).unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("chunk type must be exactly 4 ASCII letters"));
+    }
+
+    #[test]
+    fn test_prompt_encode_args() {
+        let args = encode_args(std::path::Path::new("in.png"), "unused");
+        let args = args::EncodeArgs {
+            chunk_type: None,
+            ..args
+        };
+
+        // 块类型和输出文件使用默认值
+        let mut input = "\nhello there\n\n".as_bytes();
+        let mut output = Vec::new();
+        let prompted = prompt_encode_args(&args, &mut input, &mut output).unwrap();
+        assert_eq!(prompted.chunk_type.as_deref(), Some("ruSt"));
+        assert_eq!(prompted.message.message.as_deref(), Some("hello there"));
+        assert_eq!(prompted.output, Some(PathBuf::from("in.png")));
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "chunk type [ruSt]: message: output file [in.png]: "
+        );
+
+        let mut input = "abCd\nhi\nout.png\n".as_bytes();
+        let prompted = prompt_encode_args(&args, &mut input, &mut Vec::new()).unwrap();
+        assert_eq!(prompted.chunk_type.as_deref(), Some("abCd"));
+        assert_eq!(prompted.output, Some(PathBuf::from("out.png")));
+
+        // 信息不能为空，非法的块类型直接报错
+        assert!(prompt_encode_args(&args, &mut "\n\n".as_bytes(), &mut Vec::new()).is_err());
+        assert!(prompt_encode_args(&args, &mut "rust\n".as_bytes(), &mut Vec::new()).is_err());
+    }
+
+    #[test]
+    fn test_encode_summary() {
+        let chunk_type = ChunkType::from_str("ruSt").unwrap();
+        let chunks = [Chunk::new(chunk_type, b"hello".to_vec())];
+        assert_eq!(
+            encode_summary("ruSt", &chunks, 100, 117, std::path::Path::new("out.png")),
+            "wrote 1 ruSt chunk (17 bytes) to out.png: 100 -> 117 bytes"
+        );
+
+        let chunks = [
+            Chunk::new(chunk_type, b"ab".to_vec()),
+            Chunk::new(chunk_type, b"cd".to_vec()),
+        ];
+        assert_eq!(
+            encode_summary("ruSt", &chunks, 100, 128, std::path::Path::new("-")),
+            "wrote 2 ruSt chunks (28 bytes) to -: 100 -> 128 bytes"
+        );
+    }
+
+    #[test]
+    fn test_encode_refuses_duplicated_iend() {
+        let path = testing_png_file(
+            "encode-duplicated-iend",
+            &[("IHDR", "header"), ("IEND", ""), ("IEND", "")],
+        );
+        let refused = Commands::default().encode(&encode_args(&path, "message"));
+        let unchanged = Commands::default().png_from_file(&path).unwrap();
+        let args = args::EncodeArgs {
+            force: true,
+            ..encode_args(&path, "message")
+        };
+        let forced = Commands::default().encode(&args);
+        let png = Commands::default().png_from_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(refused
+            .unwrap_err()
+            .to_string()
+            .ends_with("IEND appears 2 times; use `--force` to write anyway"));
+        assert!(unchanged.chunk_by_type("ruSt").is_none());
+        assert!(forced.is_ok());
+        assert!(png.chunk_by_type("ruSt").is_some());
+    }
+
+    #[test]
+    fn test_encode_after_and_before() {
+        let path = testing_png_file(
+            "encode-anchor",
+            &[
+                ("IHDR", "header"),
+                ("tEXt", "a\0b"),
+                ("IDAT", "data"),
+                ("IEND", ""),
+            ],
+        );
+        let types = |path: &PathBuf| -> Vec<String> {
+            let png = Commands::default().png_from_file(path).unwrap();
+            png.iter_chunks()
+                .map(|c| c.chunk_type().to_string())
+                .collect()
+        };
+
+        let after = Commands::default().encode(&args::EncodeArgs {
+            after: Some("tEXt".to_string()),
+            ..encode_args(&path, "message")
+        });
+        let after_types = types(&path);
+        let before = Commands::default().encode(&args::EncodeArgs {
+            before: Some("tEXt".to_string()),
+            ..encode_args(&path, "message")
+        });
+        let before_types = types(&path);
+        let missing = Commands::default().encode(&args::EncodeArgs {
+            after: Some("zTXt".to_string()),
+            ..encode_args(&path, "message")
+        });
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(after.is_ok());
+        assert_eq!(after_types, ["IHDR", "tEXt", "ruSt", "IDAT", "IEND"]);
+        assert!(before.is_ok());
+        assert_eq!(before_types, ["IHDR", "ruSt", "tEXt", "IDAT", "IEND"]);
+        assert_eq!(
+            missing.unwrap_err().to_string(),
+            "anchor chunk `zTXt` not found"
+        );
+    }
+
+    #[test]
+    fn test_encode_keeps_trailing_data() {
+        let path = testing_png_file("encode-trailing", &[("IHDR", "header"), ("IEND", "")]);
+        let mut bytes = std::fs::read(&path).unwrap();
+        bytes.extend_from_slice(b"junk after IEND");
+        std::fs::write(&path, &bytes).unwrap();
+
+        let result = Commands::default().encode(&encode_args(&path, "message"));
+        let kept = std::fs::read(&path).unwrap();
+        let args = args::EncodeArgs {
+            strip_trailing: true,
+            ..encode_args(&path, "message")
+        };
+        let strip_result = Commands::default().encode(&args);
+        let stripped = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(result.is_ok());
+        assert!(strip_result.is_ok());
+
+        assert!(kept.ends_with(b"IEND\xaeB`\x82junk after IEND"));
+        assert!(stripped.ends_with(b"IEND\xaeB`\x82"));
+    }
+
+    #[test]
+    fn test_encode_backup() {
+        let path = testing_png_file("encode-backup", &[("IHDR", "header"), ("IEND", "")]);
+        let original = std::fs::read(&path).unwrap();
+        let args = args::EncodeArgs {
+            no_backup: false,
+            ..encode_args(&path, "message")
+        };
+        let result = Commands::default().encode(&args);
+        let backup = std::fs::read(backup_path(&path));
+        let edited = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let _ = std::fs::remove_file(backup_path(&path));
+        assert!(result.is_ok());
+
+        assert_eq!(backup.unwrap(), original);
+        assert_ne!(edited, original);
+    }
+
+    #[test]
+    fn test_encode_stdout_rejects_verify_with() {
+        let path = testing_png_file("encode-stdout", &[("IHDR", "header"), ("IEND", "")]);
+        let args = args::EncodeArgs {
+            output: Some(PathBuf::from("-")),
+            verify_with: Some("true".to_string()),
+            ..encode_args(&path, "message")
+        };
+        let result = Commands::default().encode(&args);
+        std::fs::remove_file(&path).unwrap();
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_encode_decode_split() {
+        let path = testing_png_file("encode-split", &[("IHDR", "header"), ("IEND", "")]);
+        let message: String = (0..5000).map(|i| (b'a' + (i % 26) as u8) as char).collect();
+        let args = args::EncodeArgs {
+            split: Some(1000),
+            ..encode_args(&path, &message)
+        };
+        Commands::default().encode(&args).unwrap();
+        let png = Commands::default().png_from_file(&path).unwrap();
+        assert_eq!(png.chunk_count_by_type("ruSt"), 5);
+
+        let output = path.with_extension("decoded");
+        let args = args::DecodeArgs {
+            output: Some(output.clone()),
+            ..decode_args(&path)
+        };
+        let result = Commands::default().decode(&args);
+        let decoded = std::fs::read(&output);
+        std::fs::remove_file(&path).unwrap();
+        let _ = std::fs::remove_file(&output);
+        assert!(result.is_ok());
+        assert_eq!(decoded.unwrap(), message.as_bytes());
+    }
+
+    #[test]
+    fn test_decode_format_alias() {
+        use clap::Parser;
+
+        let args = args::Args::try_parse_from([
+            "pngme", "decode", "a.png", "-c", "ruSt", "--format", "base64",
+        ])
+        .unwrap();
+        let Some(args::Commands::Decode(decode)) = args.command else {
+            panic!("expected decode command");
+        };
+        assert_eq!(decode.encoding, args::DataEncoding::Base64);
+    }
+
+    #[test]
+    fn test_extract_all() {
+        let path = testing_png_file(
+            "extract-all",
+            &[
+                ("IHDR", "header"),
+                ("tEXt", "a\0b"),
+                ("IDAT", "data"),
+                ("ruSt", "first"),
+                ("ruSt", "second"),
+                ("IEND", ""),
+            ],
+        );
+        let out_dir = path.with_extension("extracted");
+        let result = Commands::default().extract_all(&path, &out_dir);
+        let mut files: Vec<String> = std::fs::read_dir(&out_dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        files.sort();
+        let second = std::fs::read(out_dir.join("ruSt_4.bin"));
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_dir_all(&out_dir).unwrap();
+
+        assert!(result.is_ok());
+        assert_eq!(files, ["ruSt_3.bin", "ruSt_4.bin", "tEXt_1.bin"]);
+        assert_eq!(second.unwrap(), b"second");
+    }
+
+    #[test]
+    fn test_write_completions() {
+        let mut output = Vec::new();
+        write_completions(clap_complete::Shell::Bash, &mut output);
+        let script = String::from_utf8(output).unwrap();
+        assert!(script.contains("_pngme()"));
+        assert!(script.contains("extract-trailing-png"));
+        assert!(script.contains("completions"));
+    }
+
+    #[test]
+    fn test_join_fragments() {
+        let chunk_type = ChunkType::from_str("ruSt").unwrap();
+        let chunks: Vec<Chunk> = split_message(b"hello world", 4)
+            .unwrap()
+            .into_iter()
+            .rev()
+            .map(|data| Chunk::new(chunk_type, data))
+            .collect();
+        let refs: Vec<&Chunk> = chunks.iter().collect();
+        let joined = join_fragments(&refs).unwrap().unwrap();
+        assert_eq!(joined.data(), b"hello world");
+
+        // 缺少一段时数量对不上，按普通块处理
+        assert!(join_fragments(&refs[1..]).unwrap().is_none());
+
+        let plain = Chunk::new(chunk_type, b"plain".to_vec());
+        assert!(join_fragments(&[&plain]).unwrap().is_none());
+        // 恰好以 PS01 开头的普通信息
+        let prefixed = Chunk::new(chunk_type, b"PS01abcdefgh".to_vec());
+        assert!(join_fragments(&[&prefixed]).unwrap().is_none());
+        assert!(join_fragments(&[refs[0], &plain]).unwrap().is_none());
+        assert!(split_message(b"hi", 0).is_err());
+    }
+
+    #[test]
+    fn test_unpack_message() {
+        // 不压缩时数据保持不变
+        assert_eq!(pack_message(b"hi".to_vec(), None).unwrap(), b"hi");
+        assert_eq!(unpack_message(b"hi", false).unwrap(), b"hi");
+        assert!(unpack_message(b"hi", true).is_err());
+
+        let packed = pack_message(b"hi".to_vec(), Some(Compression::default())).unwrap();
+        assert_eq!(unpack_message(&packed, false).unwrap(), b"hi");
+
+        // 恰好以 PZ01 开头的普通信息原样返回，--decompress 时报错
+        assert_eq!(unpack_message(b"PZ01 notes", false).unwrap(), b"PZ01 notes");
+        assert!(unpack_message(b"PZ01 notes", true).is_err());
+    }
+
+    #[test]
+    fn test_decode_plain_message_with_split_prefix() {
+        let path = testing_png_file("plain-ps01", &[("IHDR", "header"), ("IEND", "")]);
+        let output = path.with_extension("bin");
+        let encoded = Commands::default().encode(&encode_args(&path, "PS01abcdefgh"));
+        let decoded = Commands::default().decode(&args::DecodeArgs {
+            output: Some(output.clone()),
+            ..decode_args(&path)
+        });
+        let written = std::fs::read(&output);
+        std::fs::remove_file(&path).unwrap();
+        let _ = std::fs::remove_file(&output);
+        assert!(encoded.is_ok());
+        assert!(decoded.is_ok());
+        assert_eq!(written.unwrap(), b"PS01abcdefgh");
+    }
+
+    #[test]
+    fn test_decode_plain_message_with_compressed_prefix() {
+        let path = testing_png_file("plain-pz01", &[("IHDR", "header"), ("IEND", "")]);
+        let output = path.with_extension("bin");
+        let encoded = Commands::default().encode(&encode_args(&path, "PZ01 notes"));
+        let decoded = Commands::default().decode(&args::DecodeArgs {
+            output: Some(output.clone()),
+            ..decode_args(&path)
+        });
+        let written = std::fs::read(&output);
+        std::fs::remove_file(&path).unwrap();
+        let _ = std::fs::remove_file(&output);
+        assert!(encoded.is_ok());
+        assert!(decoded.is_ok());
+        assert_eq!(written.unwrap(), b"PZ01 notes");
+    }
+
+    #[test]
+    fn test_encode_message_file_round_trip() {
+        let path = testing_png_file("message-file", &[("IHDR", "header"), ("IEND", "")]);
+        let secret = path.with_extension("secret");
+        let restored = path.with_extension("restored");
+        let payload: Vec<u8> = (0..=255).collect();
+        std::fs::write(&secret, &payload).unwrap();
+
+        let encode = args::EncodeArgs {
+            message: args::MessageArgs {
+                message: None,
+                message_file: Some(secret.clone()),
+                stdin: false,
+                interactive: false,
+            },
+            ..encode_args(&path, "")
+        };
+        let decode = args::DecodeArgs {
+            output: Some(restored.clone()),
+            ..decode_args(&path)
+        };
+        let encoded = Commands::default().encode(&encode);
+        let decoded = Commands::default().decode(&decode);
+        let written = std::fs::read(&restored);
+        for file in [&path, &secret, &restored] {
+            let _ = std::fs::remove_file(file);
+        }
+        assert!(encoded.is_ok());
+        assert!(decoded.is_ok());
+        assert_eq!(written.unwrap(), payload);
+    }
+
+    #[test]
+    fn test_encode_keeps_iend_last() {
+        let path = testing_png_file("encode-iend", &[("IHDR", "header"), ("IEND", "")]);
+        let args = encode_args(&path, "hidden");
+        let result = Commands::default().encode(&args);
+        let png = Commands::default().png_from_file(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert!(result.is_ok());
+
+        let png = png.unwrap();
+        let types: Vec<String> = png
+            .chunks()
+            .iter()
+            .map(|c| c.chunk_type().to_string())
+            .collect();
+        assert_eq!(types, ["IHDR", "ruSt", "IEND"]);
+    }
+
+    #[test]
+    fn test_encode_at() {
+        let path = testing_png_file(
+            "encode-at",
+            &[("IHDR", "header"), ("IDAT", "data"), ("IEND", "")],
+        );
+        let at_start = args::EncodeArgs {
+            at: Some(1),
+            ..encode_args(&path, "hidden")
+        };
+        let out_of_range = args::EncodeArgs {
+            at: Some(9),
+            ..encode_args(&path, "hidden")
+        };
+        let result = Commands::default().encode(&at_start);
+        let rejected = Commands::default().encode(&out_of_range);
+        let png = Commands::default().png_from_file(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert!(result.is_ok());
+        assert!(rejected.is_err());
+
+        let types: Vec<String> = png
+            .unwrap()
+            .chunks()
+            .iter()
+            .map(|c| c.chunk_type().to_string())
+            .collect();
+        assert_eq!(types, ["IHDR", "ruSt", "IDAT", "IEND"]);
+    }
+
+    #[test]
+    fn test_encode_moves_message_after_iend() {
+        // 旧版本会把块写在 IEND 之后，重新 encode 时应当移回 IEND 之前
+        let path = testing_png_file(
+            "encode-after-iend",
+            &[("IHDR", "header"), ("IEND", ""), ("ruSt", "old")],
+        );
+        let result = Commands::default().encode(&encode_args(&path, "new"));
+        let png = Commands::default().png_from_file(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert!(result.is_ok());
+
+        let png = png.unwrap();
+        let last = png.chunks().last().unwrap();
+        assert_eq!(&last.chunk_type().to_string(), "IEND");
+        assert_eq!(png.chunks_by_type("ruSt").len(), 1);
+        assert_eq!(png.chunk_by_type("ruSt").unwrap().data(), b"new");
+    }
+
+    #[test]
+    fn test_encode_emit_report() {
+        let path = testing_png_file("emit-report", &[("IHDR", "header"), ("IEND", "")]);
+        let report = path.with_extension("json");
+        let args = args::EncodeArgs {
+            emit_report: Some(report.clone()),
+            ..encode_args(&path, "hidden")
+        };
+        let before = std::fs::metadata(&path).unwrap().len();
+        let result = Commands::default().encode(&args);
+        let after = std::fs::metadata(&path).unwrap().len();
+        let written = std::fs::read_to_string(&report);
+        std::fs::remove_file(&path).unwrap();
+        let _ = std::fs::remove_file(&report);
+        assert!(result.is_ok());
+
+        let json: serde_json::Value = serde_json::from_str(&written.unwrap()).unwrap();
+        assert_eq!(json["added"][0]["type"], "ruSt");
+        assert_eq!(json["added"][0]["length"], 6);
+        assert_eq!(json["removed"].as_array().unwrap().len(), 0);
+        assert_eq!(json["before"]["size"], before);
+        assert_eq!(json["after"]["size"], after);
+        assert_eq!(after, before + 18);
+    }
+
+    #[test]
+    fn test_read_with_timeout() {
+        // 写入端一直保持打开但不写入数据，读取会永远阻塞
+        let (reader, _writer) = std::io::pipe().unwrap();
+        let result = read_with_timeout(Some(Duration::from_millis(100)), move || Ok(reader));
+        assert!(result.is_err());
+
+        let data = read_with_timeout(Some(Duration::from_secs(5)), || Ok(&b"hello"[..])).unwrap();
+        assert_eq!(data, b"hello");
+    }
+
+    #[test]
+    fn test_shannon_entropy() {
+        assert_eq!(shannon_entropy(b""), 0.0);
+        assert_eq!(shannon_entropy(b"aaaa"), 0.0);
+        assert_eq!(shannon_entropy(b"abab"), 1.0);
+
+        let all: Vec<u8> = (0..=255).collect();
+        assert_eq!(shannon_entropy(&all), 8.0);
+    }
+
+    #[test]
+    fn test_not_found_message() {
+        assert_eq!(
+            not_found_message("iHDR"),
+            "`iHDR` message not exists; did you mean `IHDR`?"
+        );
+        assert_eq!(not_found_message("ruSt"), "`ruSt` message not exists");
+    }
+
+    #[test]
+    fn test_list_text_skips_malformed() {
+        let path = testing_png_file(
+            "list-text",
+            &[
+                ("IHDR", "header"),
+                ("tEXt", "Author\0Jane"),
+                ("tEXt", "no separator"),
+            ],
+        );
+        let result = Commands::default().list_text(&path, false);
+        std::fs::remove_file(&path).unwrap();
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_group_by_keyword() {
+        let entry = |k: &str, v: &str| (k.to_string(), v.to_string());
+        let groups = group_by_keyword(vec![
+            entry("Author", "Jane"),
+            entry("Comment", "first"),
+            entry("Author", "John"),
+        ]);
+        assert_eq!(
+            groups,
+            vec![
+                (
+                    "Author".to_string(),
+                    vec!["Jane".to_string(), "John".to_string()]
+                ),
+                ("Comment".to_string(), vec!["first".to_string()]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_repair_crc() {
+        let path = testing_png_file(
+            "repair-crc",
+            &[("IHDR", "header"), ("ruSt", "hey"), ("IEND", "")],
+        );
+        // 破坏 ruSt 的 crc 的最后一个字节
+        let mut bytes = std::fs::read(&path).unwrap();
+        let crc_end = 8 + (12 + 6) + (12 + 3);
+        bytes[crc_end - 1] ^= 0xff;
+        std::fs::write(&path, &bytes).unwrap();
+        assert!(Png::try_from(bytes.as_slice()).is_err());
+
+        let output = path.with_extension("repaired.png");
+        Commands::default()
+            .repair_crc(&path, Some(&output))
+            .unwrap();
+        let repaired = std::fs::read(&output).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(&output).unwrap();
+
+        let png = Png::try_from(repaired.as_slice()).unwrap();
+        assert_eq!(png.chunk_by_type("ruSt").unwrap().data(), b"hey");
+    }
+
+    #[test]
+    fn test_strip_to_output() {
+        let path = testing_png_file(
+            "strip",
+            &[
+                ("IHDR", "header"),
+                ("tEXt", "a\0b"),
+                ("IDAT", "data"),
+                ("IEND", ""),
+            ],
+        );
+        let output = path.with_extension("stripped.png");
+        let result = Commands::default().strip(&path, false, None, Some(&output));
+        let original = Commands::default().png_from_file(&path);
+        let stripped = Commands::default().png_from_file(&output);
+        std::fs::remove_file(&path).unwrap();
+        let _ = std::fs::remove_file(&output);
+        assert!(result.is_ok());
+
+        assert_eq!(original.unwrap().chunk_count(), 4);
+        let types: Vec<String> = stripped
+            .unwrap()
+            .chunks()
+            .iter()
+            .map(|c| c.chunk_type().to_string())
+            .collect();
+        assert_eq!(types, ["IHDR", "IDAT", "IEND"]);
+    }
+
+    #[test]
+    fn test_diff_chunks() {
+        let chunk = |ty: &str, data: &str| {
+            Chunk::new(ChunkType::from_str(ty).unwrap(), data.as_bytes().to_vec())
+        };
+        let a = Png::from_chunks(vec![
+            chunk("IHDR", "header"),
+            chunk("teXt", "old"),
+            chunk("IDAT", "one"),
+            chunk("IDAT", "two"),
+            chunk("IEND", ""),
+        ]);
+        let b = Png::from_chunks(vec![
+            chunk("IHDR", "header"),
+            chunk("IDAT", "one"),
+            chunk("IDAT", "2"),
+            chunk("ruSt", "message"),
+            chunk("IEND", ""),
+        ]);
+
+        let lines: Vec<String> = diff_chunks(&a, &b)
+            .iter()
+            .filter(|diff| **diff != ChunkDiff::Same)
+            .map(|diff| diff.to_string())
+            .collect();
+        let (old, new) = (chunk("IDAT", "two").crc(), chunk("IDAT", "2").crc());
+        assert_eq!(
+            lines,
+            [
+                "- teXt (3 bytes)".to_string(),
+                format!("~ IDAT (3 -> 1 bytes, crc {old:#010x} -> {new:#010x})"),
+                "+ ruSt (7 bytes)".to_string(),
+            ]
+        );
+        assert!(diff_chunks(&a, &a)
+            .iter()
+            .all(|diff| *diff == ChunkDiff::Same));
+    }
+
+    #[test]
+    fn test_looks_like_message() {
+        let chunk = |ty: &str, data: &str| {
+            Chunk::new(ChunkType::from_str(ty).unwrap(), data.as_bytes().to_vec())
+        };
+        assert!(looks_like_message(&chunk("ruSt", "hidden")));
+        assert!(!looks_like_message(&chunk("ruSt", "")));
+        assert!(!looks_like_message(&chunk("tEXt", "Author\0Jane")));
+        assert!(!looks_like_message(&chunk("RuSt", "critical")));
+        assert!(!looks_like_message(&chunk("rUSt", "public")));
+    }
+
+    #[test]
+    fn test_count() {
+        let path = testing_png_file("count", &[("IHDR", "header"), ("ruSt", "a"), ("ruSt", "b")]);
+        let commands = Commands::default();
+        assert!(commands.count(&path, None).is_ok());
+        assert!(commands.count(&path, Some("ruSt")).is_ok());
+        let missing = commands.count(&path, Some("IDAT"));
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(missing.unwrap_err().to_string(), "no `IDAT` chunks found");
+    }
+
+    #[test]
+    fn test_mmap_matches_read() {
+        let path = testing_png_file(
+            "mmap",
+            &[("IHDR", "header"), ("ruSt", "message"), ("IEND", "")],
+        );
+        let (read, mapped) = (Commands::default(), Commands::new(false, false, true));
+        let pngs = (
+            read.png_for_reading(&path).unwrap(),
+            mapped.png_for_reading(&path).unwrap(),
+        );
+        let results = [&read, &mapped].map(|commands| {
+            (
+                commands.print(&path, false, false).is_ok(),
+                commands.list(&path, None, args::OutputFormat::Text).is_ok(),
+                commands.count(&path, Some("ruSt")).is_ok(),
+                commands.check(&path, false, true, false, None).is_ok(),
+            )
+        });
+
+        // crc 错误的文件两种方式都会报错
+        let mut bytes = std::fs::read(&path).unwrap();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0xff;
+        std::fs::write(&path, &bytes).unwrap();
+        let bad_count = [&read, &mapped].map(|commands| commands.count(&path, None).is_err());
+        let bad_check = mapped.check(&path, false, true, false, None).is_err();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(pngs.0.as_bytes(), pngs.1.as_bytes());
+        assert_eq!(results, [(true, true, true, true); 2]);
+        assert_eq!(bad_count, [true, true]);
+        assert!(bad_check);
+    }
+
+    #[test]
+    fn test_removed_message() {
+        let chunk = Chunk::new(ChunkType::from_str("ruSt").unwrap(), b"hey".to_vec());
+        assert_eq!(
+            removed_message(&chunk),
+            format!("removed ruSt (3 bytes, crc {:#010x})", chunk.crc())
+        );
+    }
+
+    #[test]
+    fn test_color_type_name() {
+        assert_eq!(color_type_name(0), "Grayscale");
+        assert_eq!(color_type_name(6), "RGBA");
+        assert_eq!(color_type_name(5), "5");
+    }
+
+    #[test]
+    fn test_validate_command() {
+        let path = testing_png_file(
+            "validate",
+            &[("IHDR", "header"), ("IDAT", "data"), ("IEND", "")],
+        );
+        let valid = Commands::default().validate(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert!(valid.is_ok());
+
+        let path = testing_png_file("validate-no-idat", &[("IHDR", "header"), ("IEND", "")]);
+        let invalid = Commands::default().validate(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            invalid.unwrap_err().to_string(),
+            "1 structural error(s) found"
+        );
+    }
+
+    #[test]
+    fn test_info_without_ihdr() {
+        let path = testing_png_file("info-missing-ihdr", &[("ruSt", "message")]);
+        let result = Commands::default().info(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(result.unwrap_err().to_string(), "missing IHDR chunk");
+    }
+
+    #[test]
+    fn test_preview_bytes() {
+        assert_eq!(preview_bytes(b"hello world", 4), "68 65 6c 6c |hell|…");
+        assert_eq!(preview_bytes(b"hi\n", 8), "68 69 0a |hi.|");
+        assert_eq!(preview_bytes(b"", 8), " ||");
+    }
+
+    #[test]
+    fn test_chunk_infos_json() {
+        let chunk_type = ChunkType::from_str("ruSt").unwrap();
+        let chunk = Chunk::new(chunk_type, b"hello".to_vec());
+        let crc = chunk.crc();
+        let png = Png::from_chunks(vec![chunk]);
+
+        let json = serde_json::to_value(chunk_infos(&png)).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!([{
+                "type": "ruSt",
+                "length": 5,
+                "crc": crc,
+                "crc_valid": true,
+                "offset": 8,
+                "critical": false,
+                "public": false,
+                "safe_to_copy": true,
+            }])
+        );
+    }
+
+    #[test]
+    fn test_chunk_type_label() {
+        assert_eq!(
+            chunk_type_label(&ChunkType::from_str("ruSt").unwrap()),
+            "ruSt"
+        );
+        let malformed = ChunkType::from_bytes_unchecked([b'r', 0xff, b'\n', b'"']);
+        assert_eq!(chunk_type_label(&malformed), "r\\xff\\n\\\"");
+    }
+
+    #[test]
+    fn test_is_stdio() {
+        assert!(is_stdio(std::path::Path::new("-")));
+        assert!(!is_stdio(std::path::Path::new("./-")));
+        assert!(!is_stdio(std::path::Path::new("image.png")));
+    }
+
+    #[test]
+    fn test_hex_dump() {
+        let data: Vec<u8> = (0..18).collect();
+        assert_eq!(
+            hex_dump(&data),
+            "00000000  00 01 02 03 04 05 06 07 08 09 0a 0b 0c 0d 0e 0f\n00000010  10 11\n"
+        );
+    }
+}
@@ -0,0 +1,69 @@
+//! 使用口令加密隐藏的信息。
+//!
+//! 加密后的数据格式为：版本号（1 字节）+ salt（16 字节）+ nonce（12 字节）+ 密文，
+//! 密钥由口令和 salt 经过 PBKDF2-HMAC-SHA256 派生，使用 ChaCha20-Poly1305 加密
+
+use chacha20poly1305::{aead::Aead, ChaCha20Poly1305, Key, KeyInit, Nonce};
+use pngme::{Error, Result};
+
+const VERSION: u8 = 1;
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+const KDF_ROUNDS: u32 = 100_000;
+
+pub fn encrypt(plaintext: &[u8], password: &str) -> Result<Vec<u8>> {
+    let mut salt = [0; SALT_LEN];
+    let mut nonce = [0; NONCE_LEN];
+    getrandom::fill(&mut salt).map_err(|e| Error::from(e.to_string()))?;
+    getrandom::fill(&mut nonce).map_err(|e| Error::from(e.to_string()))?;
+
+    let ciphertext = cipher(password, &salt)
+        .encrypt(Nonce::from_slice(&nonce), plaintext)
+        .map_err(|_| Error::from("encryption failed"))?;
+
+    let mut data = vec![VERSION];
+    data.extend(salt);
+    data.extend(nonce);
+    data.extend(ciphertext);
+    Ok(data)
+}
+
+pub fn decrypt(data: &[u8], password: &str) -> Result<Vec<u8>> {
+    let header = 1 + SALT_LEN + NONCE_LEN;
+    if data.len() < header || data[0] != VERSION {
+        return Err(Error::from("message is not encrypted by pngme"));
+    }
+
+    let salt = &data[1..1 + SALT_LEN];
+    let nonce = &data[1 + SALT_LEN..header];
+    cipher(password, salt)
+        .decrypt(Nonce::from_slice(nonce), &data[header..])
+        .map_err(|_| Error::from("decryption failed: wrong password or corrupted message"))
+}
+
+fn cipher(password: &str, salt: &[u8]) -> ChaCha20Poly1305 {
+    let mut key = [0; 32];
+    pbkdf2::pbkdf2_hmac::<sha2::Sha256>(password.as_bytes(), salt, KDF_ROUNDS, &mut key);
+    ChaCha20Poly1305::new(Key::from_slice(&key))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encrypt_round_trip() {
+        let data = encrypt(b"secret note", "hunter2").unwrap();
+        assert_eq!(data[0], VERSION);
+        assert!(!data.windows(6).any(|w| w == b"secret"));
+        assert_eq!(decrypt(&data, "hunter2").unwrap(), b"secret note");
+    }
+
+    #[test]
+    fn test_decrypt_wrong_password() {
+        let data = encrypt(b"secret note", "hunter2").unwrap();
+        let err = decrypt(&data, "hunter3").unwrap_err();
+        assert!(err.to_string().contains("wrong password"));
+        assert!(decrypt(b"plain text", "hunter2").is_err());
+    }
+}
@@ -0,0 +1,64 @@
+use thiserror::Error;
+
+pub type PngResult<T> = std::result::Result<T, PngError>;
+
+/// 解析 PNG 文件、块以及块类型时可能出现的错误
+#[derive(Debug, Error)]
+pub enum PngError {
+    #[error("not a PNG file: bad signature")]
+    BadSignature,
+    /// expected 为根据数据计算出的 crc，actual 为文件中保存的 crc
+    #[error("CRC mismatch (stored {actual:#010x}, computed {expected:#010x})")]
+    CrcMismatch { expected: u32, actual: u32 },
+    #[error("invalid chunk type `{0}`")]
+    InvalidChunkType(String),
+    #[error("chunk type `{0}` does not conform to the PNG spec")]
+    NonConformingChunkType(String),
+    #[error("chunk data too long: {0} bytes does not fit in a u32 length")]
+    DataTooLong(usize),
+    #[error("chunk too short: need {needed} bytes, got {actual}")]
+    ChunkTooShort { needed: usize, actual: usize },
+    #[error("chunk index {index} out of range for {len} chunks")]
+    IndexOutOfRange { index: usize, len: usize },
+    #[error("cannot insert chunk at index {index}: must be between {min} and {max}")]
+    InvalidInsertPosition {
+        index: usize,
+        min: usize,
+        max: usize,
+    },
+    #[error("missing IHDR chunk")]
+    MissingIhdr,
+    #[error("malformed IHDR chunk: expected 13 bytes, got {0}")]
+    MalformedIhdr(usize),
+    #[error("invalid tEXt keyword `{0}`: must be 1-79 printable Latin-1 characters")]
+    InvalidTextKeyword(String),
+    #[error("tEXt value contains characters outside Latin-1")]
+    NonLatin1Text,
+    /// 解析文件时某个块出错，携带块的序号、类型和偏移
+    #[error(transparent)]
+    Chunk(Box<ChunkError>),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+/// 宽松解析时记录的单个块的错误，携带块的序号、类型以及在文件中的偏移
+#[derive(Debug, Error)]
+#[error("chunk {index} ({chunk_type}) at offset {offset}: {source}")]
+pub struct ChunkError {
+    pub index: usize,
+    pub chunk_type: String,
+    pub offset: usize,
+    pub source: PngError,
+}
+
+impl ChunkError {
+    /// chunk_type 为文件中的原始 4 个字节，可能不是合法的块类型
+    pub fn wrap(index: usize, chunk_type: &[u8], offset: usize, source: PngError) -> PngError {
+        PngError::Chunk(Box::new(ChunkError {
+            index,
+            chunk_type: String::from_utf8_lossy(chunk_type).to_string(),
+            offset,
+            source,
+        }))
+    }
+}
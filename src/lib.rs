@@ -0,0 +1,30 @@
+//! 在 PNG 文件的块中隐藏信息
+//!
+//! ```
+//! use std::str::FromStr;
+//!
+//! use pngme::{Chunk, ChunkType, Png};
+//!
+//! let mut png = Png::from_chunks(Vec::new());
+//! let chunk_type = ChunkType::from_str("ruSt")?;
+//! png.append_chunk(Chunk::new(chunk_type, b"hidden message".to_vec()));
+//!
+//! let bytes = png.as_bytes();
+//! let decoded = Png::try_from(bytes.as_slice())?;
+//! let chunk = decoded.chunk_by_type("ruSt").unwrap();
+//! assert_eq!(chunk.data_as_string()?, "hidden message");
+//! # Ok::<(), pngme::Error>(())
+//! ```
+
+pub mod chunk;
+pub mod chunk_type;
+pub mod error;
+pub mod png;
+
+pub use chunk::{Chunk, ChunkReader};
+pub use chunk_type::ChunkType;
+pub use error::{ChunkError, PngError};
+pub use png::{Ihdr, Png, Severity, ValidationIssue};
+
+pub type Error = Box<dyn std::error::Error>;
+pub type Result<T> = anyhow::Result<T, Error>;
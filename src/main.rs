@@ -1,16 +1,24 @@
+use std::process::ExitCode;
+
 use clap::Parser;
-use commands::Commands;
+use commands::{Commands, NotFound};
 
 mod args;
-mod chunk;
-mod chunk_type;
 mod commands;
-mod png;
-
-pub type Error = Box<dyn std::error::Error>;
-pub type Result<T> = anyhow::Result<T, Error>;
+mod crypto;
+mod manifest;
+mod report;
 
-fn main() -> Result<()> {
-    let commands = Commands;
-    commands.process(args::Args::parse())
+/// 退出码：0 表示成功，1 表示出错，2 表示 `decode`/`check` 没有找到隐藏的信息
+fn main() -> ExitCode {
+    let args = args::Args::parse();
+    let commands = Commands::new(args.strict_spec, args.quiet, args.mmap);
+    match commands.process(args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) if e.is::<NotFound>() => ExitCode::from(2),
+        Err(e) => {
+            eprintln!("Error: {e}");
+            ExitCode::FAILURE
+        }
+    }
 }
@@ -0,0 +1,93 @@
+//! PNG 结构的 JSON 清单，可以从清单重新构造出相同的 PNG
+
+use std::str::FromStr;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use pngme::{chunk::Chunk, chunk_type::ChunkType, png::Png, Error, Result};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Manifest {
+    chunks: Vec<ChunkEntry>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ChunkEntry {
+    #[serde(rename = "type")]
+    chunk_type: String,
+    length: usize,
+    /// 文件中保存的 crc
+    crc: u32,
+    offset: usize,
+    critical: bool,
+    /// base64 编码的块数据，重建 PNG 时需要所有块的数据，所以关键块也会包含
+    data: String,
+}
+
+impl Manifest {
+    pub fn from_png(png: &Png) -> Self {
+        let chunks = png
+            .chunks()
+            .iter()
+            .enumerate()
+            .map(|(index, chunk)| ChunkEntry {
+                chunk_type: chunk.chunk_type().to_string(),
+                length: chunk.length(),
+                crc: chunk.stored_crc(),
+                offset: png.byte_range_of(index).unwrap_or_default().start,
+                critical: chunk.chunk_type().is_critical(),
+                data: STANDARD.encode(chunk.data()),
+            })
+            .collect();
+        Manifest { chunks }
+    }
+
+    /// 按清单重新构造 PNG，块数据与长度或 crc 不一致时报错
+    pub fn to_png(&self) -> Result<Png> {
+        let mut chunks = Vec::with_capacity(self.chunks.len());
+        for (index, entry) in self.chunks.iter().enumerate() {
+            let chunk_type = ChunkType::from_str(&entry.chunk_type)?;
+            let chunk = Chunk::new(chunk_type, STANDARD.decode(&entry.data)?);
+            if chunk.length() != entry.length || chunk.crc() != entry.crc {
+                return Err(Error::from(format!(
+                    "manifest entry {index} ({}) does not match its data",
+                    entry.chunk_type
+                )));
+            }
+            chunks.push(chunk);
+        }
+        Ok(Png::from_chunks(chunks))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_manifest_round_trip() {
+        let chunks = vec![
+            Chunk::new(ChunkType::from_str("IHDR").unwrap(), vec![0, 0, 0, 1]),
+            Chunk::new(ChunkType::from_str("ruSt").unwrap(), b"hidden".to_vec()),
+            Chunk::new(ChunkType::from_str("IEND").unwrap(), Vec::new()),
+        ];
+        let png = Png::from_chunks(chunks);
+
+        let json = serde_json::to_string(&Manifest::from_png(&png)).unwrap();
+        let manifest: Manifest = serde_json::from_str(&json).unwrap();
+        let rebuilt = manifest.to_png().unwrap();
+        assert_eq!(rebuilt.chunks(), png.chunks());
+        assert_eq!(rebuilt.as_bytes(), png.as_bytes());
+    }
+
+    #[test]
+    fn test_manifest_mismatch() {
+        let png = Png::from_chunks(vec![Chunk::new(
+            ChunkType::from_str("ruSt").unwrap(),
+            b"hidden".to_vec(),
+        )]);
+        let mut manifest = Manifest::from_png(&png);
+        manifest.chunks[0].data = STANDARD.encode(b"changed");
+        assert!(manifest.to_png().is_err());
+    }
+}
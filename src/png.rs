@@ -1,21 +1,105 @@
 #![allow(unused)]
 
+use std::collections::BTreeMap;
 use std::fmt::Display;
-use std::io::Read;
+use std::io::{Read, Write};
+use std::ops::Range;
 
-use crate::chunk::Chunk;
+use crate::chunk::{read_full, Chunk, ChunkReader, CrcPolicy, ParseOptions};
+use crate::chunk_type::ChunkType;
+use crate::error::{ChunkError, PngError, PngResult};
 use crate::{Error, Result};
 
+/// IHDR 块中的图片元数据
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Ihdr {
+    pub width: u32,
+    pub height: u32,
+    pub bit_depth: u8,
+    pub color_type: u8,
+    pub compression: u8,
+    pub filter: u8,
+    pub interlace: u8,
+}
+
+impl Ihdr {
+    /// IHDR 块数据的固定长度
+    pub const LENGTH: usize = 13;
+}
+
+impl TryFrom<&[u8]> for Ihdr {
+    type Error = PngError;
+
+    fn try_from(data: &[u8]) -> PngResult<Self> {
+        if data.len() != Self::LENGTH {
+            return Err(PngError::MalformedIhdr(data.len()));
+        }
+        Ok(Ihdr {
+            width: u32::from_be_bytes([data[0], data[1], data[2], data[3]]),
+            height: u32::from_be_bytes([data[4], data[5], data[6], data[7]]),
+            bit_depth: data[8],
+            color_type: data[9],
+            compression: data[10],
+            filter: data[11],
+            interlace: data[12],
+        })
+    }
+}
+
+/// `Png::validate` 发现的问题的严重程度
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// `Png::validate` 发现的单个结构问题
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationIssue {
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl ValidationIssue {
+    fn error(message: impl Into<String>) -> Self {
+        ValidationIssue {
+            severity: Severity::Error,
+            message: message.into(),
+        }
+    }
+
+    fn warning(message: impl Into<String>) -> Self {
+        ValidationIssue {
+            severity: Severity::Warning,
+            message: message.into(),
+        }
+    }
+}
+
+impl Display for ValidationIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.severity {
+            Severity::Error => write!(f, "error: {}", self.message),
+            Severity::Warning => write!(f, "warning: {}", self.message),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct Png {
     chunks: Vec<Chunk>,
+    /// IEND 之后无法解析为块的字节，`as_bytes` 时原样写回
+    trailing: Vec<u8>,
 }
 
 impl Png {
-    const STANDARD_HEADER: [u8; 8] = [137, 80, 78, 71, 13, 10, 26, 10];
+    pub const STANDARD_HEADER: [u8; 8] = [137, 80, 78, 71, 13, 10, 26, 10];
 
-    fn from_chunks(chunks: Vec<Chunk>) -> Png {
-        Png { chunks }
+    pub fn from_chunks(chunks: Vec<Chunk>) -> Png {
+        Png {
+            chunks,
+            trailing: Vec::new(),
+        }
     }
 
     pub fn as_bytes(&self) -> Vec<u8> {
@@ -28,75 +112,572 @@ impl Png {
                     .collect::<Vec<u8>>()
                     .iter(),
             )
+            .chain(self.trailing.iter())
             .copied()
             .collect()
     }
 
+    /// 依次将签名、每个块以及尾部数据写入 writer，不需要先在内存中构造整个文件
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> PngResult<()> {
+        writer.write_all(&Self::STANDARD_HEADER)?;
+        for chunk in &self.chunks {
+            chunk.write_to(writer)?;
+        }
+        writer.write_all(&self.trailing)?;
+        Ok(())
+    }
+
+    /// 序列化后的文件大小，与 `as_bytes().len()` 相同
+    pub fn byte_len(&self) -> usize {
+        Self::STANDARD_HEADER.len()
+            + self.chunks.iter().map(Chunk::chunk_length).sum::<usize>()
+            + self.trailing.len()
+    }
+
+    /// IEND 之后无法解析为块的字节（例如某些隐写工具追加的数据），没有时为空
+    pub fn trailing_data(&self) -> &[u8] {
+        &self.trailing
+    }
+
+    /// 删除 IEND 之后的数据，返回被删除的字节
+    pub fn strip_trailing_data(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.trailing)
+    }
+
     pub fn chunk_by_type(&self, s: &str) -> Option<Chunk> {
-        let s = s.as_bytes();
-        for i in self.chunks.iter() {
-            if i.chunk_type().bytes() == s {
-                return Some(i.clone());
+        self.iter_chunks_by_type(s).next().cloned()
+    }
+
+    /// 按文件中的顺序返回所有指定类型的块
+    pub fn chunks_by_type(&self, s: &str) -> Vec<&Chunk> {
+        self.iter_chunks_by_type(s).collect()
+    }
+
+    /// 按文件中的顺序遍历所有指定类型的块，不分配内存
+    pub fn iter_chunks_by_type<'a, 's>(&'a self, s: &'s str) -> impl Iterator<Item = &'a Chunk> + 's
+    where
+        'a: 's,
+    {
+        self.chunks.iter().filter(move |c| c.chunk_type() == s)
+    }
+
+    /// bytes 是否以 PNG 签名开头，可以在解析前预先检查
+    pub fn has_valid_signature(bytes: &[u8]) -> bool {
+        bytes.starts_with(&Self::STANDARD_HEADER)
+    }
+
+    /// 按文件中的顺序返回所有块
+    pub fn chunks(&self) -> &[Chunk] {
+        &self.chunks
+    }
+
+    /// 按文件中的顺序遍历所有块，等价于 `for chunk in &png`
+    pub fn iter_chunks(&self) -> std::slice::Iter<'_, Chunk> {
+        self.chunks.iter()
+    }
+
+    /// 与 `TryFrom<&[u8]>` 相同，但会拒绝不符合 PNG 规范的块类型
+    /// （例如保留位不为 0）
+    pub fn try_from_strict(value: &[u8]) -> PngResult<Self> {
+        Self::parse(value, true)
+    }
+
+    /// 与 `TryFrom<&[u8]>` 相同，但 crc 不匹配的块不会中断解析：
+    /// 这些块仍然保留在 PNG 中，同时记录到返回的错误列表里
+    pub fn try_from_lenient(value: &[u8]) -> PngResult<(Self, Vec<ChunkError>)> {
+        let png = Self::read_from(
+            value,
+            ParseOptions {
+                check_crc: false,
+                ..ParseOptions::default()
+            },
+        )?;
+        let errors = png
+            .chunks
+            .iter()
+            .enumerate()
+            .filter(|(_, chunk)| !chunk.is_crc_valid())
+            .map(|(index, chunk)| ChunkError {
+                index,
+                chunk_type: chunk.chunk_type().to_string(),
+                offset: png.byte_range_of(index).unwrap_or_default().start,
+                source: PngError::CrcMismatch {
+                    expected: chunk.crc(),
+                    actual: chunk.stored_crc(),
+                },
+            })
+            .collect();
+        Ok((png, errors))
+    }
+
+    /// 从 value 的开头解析一个 PNG，返回解析出的 PNG 以及消耗的字节数。
+    /// 如果 IEND 之后紧跟着另一个 PNG 签名，则在此处停止解析，
+    /// 剩余的字节即为拼接在后面的 PNG
+    pub fn from_prefix(value: &[u8]) -> PngResult<(Self, usize)> {
+        Self::parse_prefix(value, false)
+    }
+
+    fn parse(value: &[u8], strict: bool) -> PngResult<Self> {
+        Self::read_from(
+            value,
+            ParseOptions {
+                strict,
+                ..ParseOptions::default()
+            },
+        )
+    }
+
+    /// 从 reader 中逐个读取块，不需要先把整个文件读入内存
+    pub fn from_reader<R: Read>(reader: R) -> PngResult<Self> {
+        Self::read_from(reader, ParseOptions::default())
+    }
+
+    /// 与 `from_reader` 相同，但会拒绝不符合 PNG 规范的块类型
+    pub fn from_reader_strict<R: Read>(reader: R) -> PngResult<Self> {
+        let options = ParseOptions {
+            strict: true,
+            ..ParseOptions::default()
+        };
+        Self::read_from(reader, options)
+    }
+
+    /// 与 `from_reader` 相同，但不校验 crc，可以通过 `Chunk::is_crc_valid` 检查每个块
+    pub fn from_reader_lenient<R: Read>(reader: R) -> PngResult<Self> {
+        let options = ParseOptions {
+            check_crc: false,
+            ..ParseOptions::default()
+        };
+        Self::read_from(reader, options)
+    }
+
+    /// 与 `from_reader` 相同，但不检查块类型是否由字母组成（例如包含数字），
+    /// 用于清理块类型损坏的文件
+    pub fn from_reader_unchecked_types<R: Read>(reader: R) -> PngResult<Self> {
+        let options = ParseOptions {
+            validate_type: false,
+            ..ParseOptions::default()
+        };
+        Self::read_from(reader, options)
+    }
+
+    /// 检查签名后逐个读取块，不需要先把整个文件读入内存，
+    /// 适合只需要找到某个块就可以停止的场景
+    pub fn chunk_reader<R: Read>(reader: R) -> PngResult<ChunkReader<R>> {
+        Self::chunk_reader_with(reader, ParseOptions::default())
+    }
+
+    fn chunk_reader_with<R: Read>(
+        mut reader: R,
+        options: ParseOptions,
+    ) -> PngResult<ChunkReader<R>> {
+        let mut header = [0; 8];
+        let n = read_full(&mut reader, &mut header)?;
+        if !Self::has_valid_signature(&header[..n]) {
+            return Err(PngError::BadSignature);
+        }
+        Ok(ChunkReader::with_options(
+            reader,
+            Self::STANDARD_HEADER.len(),
+            options,
+        ))
+    }
+
+    fn read_from<R: Read>(reader: R, options: ParseOptions) -> PngResult<Self> {
+        let mut reader = Self::chunk_reader_with(reader, options)?;
+        let chunks = reader.by_ref().collect::<PngResult<_>>()?;
+        Ok(Png {
+            chunks,
+            trailing: reader.into_trailing_data(),
+        })
+    }
+
+    fn parse_prefix(value: &[u8], strict: bool) -> PngResult<(Self, usize)> {
+        // 文件太短或者文件头不是 PNG 签名时，直接报错
+        if !Self::has_valid_signature(value) {
+            return Err(PngError::BadSignature);
+        }
+        let mut rest = &value[Self::STANDARD_HEADER.len()..];
+
+        let mut chunks: Vec<Chunk> = Vec::new();
+        let mut seen_iend = false;
+
+        while !rest.is_empty() {
+            if seen_iend && rest.starts_with(&Self::STANDARD_HEADER) {
+                break;
+            }
+
+            let offset = value.len() - rest.len();
+            let wrap = |source| {
+                let chunk_type = rest.get(4..8).unwrap_or_default();
+                ChunkError::wrap(chunks.len(), chunk_type, offset, source)
+            };
+            let chunk = Chunk::try_from(rest).map_err(wrap)?;
+            if strict && !chunk.chunk_type().is_valid() {
+                return Err(wrap(PngError::NonConformingChunkType(
+                    chunk.chunk_type().to_string(),
+                )));
             }
+            seen_iend |= chunk.chunk_type() == "IEND";
+            rest = &rest[chunk.chunk_length()..];
+            chunks.push(chunk);
         }
-        None
+
+        Ok((Png::from_chunks(chunks), value.len() - rest.len()))
     }
 
-    pub fn chunks(&self) -> &Vec<Chunk> {
-        &self.chunks
+    /// 检查文件结构，返回所有发现的问题而不是在第一个问题处报错：
+    /// 以 IHDR 开头且只有一个 IHDR、至少一个 IDAT、存在 IEND、crc 正确，
+    /// IEND 之后出现关键块为错误，出现辅助块为警告
+    pub fn validate(&self) -> Vec<ValidationIssue> {
+        let mut issues = Vec::new();
+        match self.chunks.first() {
+            None => issues.push(ValidationIssue::error("file has no chunks")),
+            Some(first) if first.chunk_type() != "IHDR" => issues.push(ValidationIssue::error(
+                format!("first chunk is {}, expected IHDR", first.chunk_type()),
+            )),
+            Some(_) => {}
+        }
+
+        for (chunk_type, count) in self.duplicated_singletons() {
+            issues.push(ValidationIssue::error(format!(
+                "{chunk_type} appears {count} times, expected once"
+            )));
+        }
+        if self.chunk_count_by_type("IDAT") == 0 {
+            issues.push(ValidationIssue::error("missing IDAT chunk"));
+        }
+
+        for (index, chunk) in self.chunks.iter().enumerate() {
+            if !chunk.is_crc_valid() {
+                issues.push(ValidationIssue::error(format!(
+                    "chunk {index} ({}): {}",
+                    chunk.chunk_type(),
+                    PngError::CrcMismatch {
+                        expected: chunk.crc(),
+                        actual: chunk.stored_crc(),
+                    }
+                )));
+            }
+        }
+
+        match self.chunks.iter().position(|c| c.chunk_type() == "IEND") {
+            None => issues.push(ValidationIssue::error("missing IEND chunk")),
+            Some(iend) => {
+                for (index, chunk) in self.chunks.iter().enumerate().skip(iend + 1) {
+                    let message = format!("chunk {index} ({}) after IEND", chunk.chunk_type());
+                    issues.push(if chunk.chunk_type().is_critical() {
+                        ValidationIssue::error(message)
+                    } else {
+                        ValidationIssue::warning(message)
+                    });
+                }
+            }
+        }
+        issues
     }
 
-    pub fn append_chunk(&mut self, chunk: Chunk) {
-        self.chunks.push(chunk);
+    /// 只允许出现一次的块（IHDR 和 IEND）中出现了多次的类型及其次数，
+    /// 这样的文件在插入或删除块时结果无法预料
+    pub fn duplicated_singletons(&self) -> Vec<(&'static str, usize)> {
+        ["IHDR", "IEND"]
+            .into_iter()
+            .map(|chunk_type| (chunk_type, self.chunk_count_by_type(chunk_type)))
+            .filter(|(_, count)| *count > 1)
+            .collect()
     }
 
-    pub fn remove_chunk(&mut self, s: &str) -> Option<Chunk> {
-        let s = s.as_bytes();
-        let mut chunk = None;
-        for i in self.chunks.iter() {
-            if i.chunk_type().bytes() == s {
-                chunk = Some(i.clone());
+    /// 只读取每个块的长度和类型，跳过数据且不校验 crc，返回 (类型, 数据长度) 列表。
+    /// 配合内存映射使用时只会访问块头所在的页，适合只需要统计块的场景
+    pub fn chunk_headers(bytes: &[u8]) -> PngResult<Vec<(ChunkType, usize)>> {
+        if !Self::has_valid_signature(bytes) {
+            return Err(PngError::BadSignature);
+        }
+
+        let mut headers = Vec::new();
+        let mut offset = Self::STANDARD_HEADER.len();
+        let mut seen_iend = false;
+        while offset < bytes.len() {
+            let rest = &bytes[offset..];
+            // IEND 之后拼接的另一个 PNG 是尾部数据
+            if seen_iend && rest.starts_with(&Self::STANDARD_HEADER) {
+                break;
+            }
+            let wrap = |source| {
+                let chunk_type = rest.get(4..8).unwrap_or_default();
+                ChunkError::wrap(headers.len(), chunk_type, offset, source)
+            };
+            let needed = rest
+                .get(..4)
+                .map(|len| u32::from_be_bytes([len[0], len[1], len[2], len[3]]) as usize + 12)
+                .unwrap_or(8);
+            // IEND 之后无法解析为块的字节是尾部数据，不再继续读取
+            let chunk_type = rest
+                .get(4..8)
+                .and_then(|ty| ChunkType::try_from([ty[0], ty[1], ty[2], ty[3]]).ok());
+            if seen_iend && (rest.len() < needed.max(8) || chunk_type.is_none()) {
                 break;
             }
+            if rest.len() < needed.max(8) {
+                return Err(wrap(PngError::ChunkTooShort {
+                    needed: needed.max(8),
+                    actual: rest.len(),
+                }));
+            }
+
+            let chunk_type =
+                ChunkType::try_from([rest[4], rest[5], rest[6], rest[7]]).map_err(wrap)?;
+            seen_iend |= chunk_type == "IEND";
+            headers.push((chunk_type, needed - 12));
+            offset += needed;
         }
+        Ok(headers)
+    }
 
-        if let Some(chunk) = chunk {
-            self.chunks = self
-                .chunks
-                .clone()
-                .into_iter()
-                .filter(|x| x.chunk_type() != chunk.chunk_type())
-                .collect();
-            Some(chunk)
-        } else {
-            None
+    /// 第 index 个块在序列化后的文件中所占的字节范围（包含文件头的偏移）
+    pub fn byte_range_of(&self, index: usize) -> Option<Range<usize>> {
+        let chunk = self.chunks.get(index)?;
+        let start = Self::STANDARD_HEADER.len()
+            + self.chunks[..index]
+                .iter()
+                .map(|c| c.chunk_length())
+                .sum::<usize>();
+        Some(start..start + chunk.chunk_length())
+    }
+
+    pub fn ihdr(&self) -> Option<&Chunk> {
+        self.chunks.iter().find(|c| c.chunk_type() == "IHDR")
+    }
+
+    /// 解析第一个块中的 IHDR，第一个块不是 IHDR 或长度不对时报错
+    pub fn header(&self) -> PngResult<Ihdr> {
+        match self.chunks.first() {
+            Some(chunk) if chunk.chunk_type() == "IHDR" => Ihdr::try_from(chunk.data()),
+            _ => Err(PngError::MissingIhdr),
         }
     }
-}
 
-impl TryFrom<&[u8]> for Png {
-    type Error = Error;
+    /// 从 IHDR 块中读取图片的宽和高
+    pub fn dimensions(&self) -> Option<(u32, u32)> {
+        let data = self.ihdr()?.data();
+        let width = u32::from_be_bytes(data.get(0..4)?.try_into().ok()?);
+        let height = u32::from_be_bytes(data.get(4..8)?.try_into().ok()?);
+        Some((width, height))
+    }
+
+    /// 解析所有 tEXt 块，返回 (关键字, 文本) 列表，两者都按 Latin-1 解码。
+    /// 没有 0 分隔符的块会被跳过
+    pub fn text_chunks(&self) -> Vec<(String, String)> {
+        let latin1 = |bytes: &[u8]| bytes.iter().map(|&b| b as char).collect::<String>();
+        self.chunks_by_type("tEXt")
+            .into_iter()
+            .filter_map(|chunk| {
+                let data = chunk.data();
+                let sep = data.iter().position(|&b| b == 0)?;
+                Some((latin1(&data[..sep]), latin1(&data[sep + 1..])))
+            })
+            .collect()
+    }
 
-    fn try_from(mut value: &[u8]) -> Result<Self> {
-        if value.len() <= 8 {
-            return Err(Error::from("incorrect png data"));
+    /// 写入一个 tEXt 块，已存在相同关键字的块时原地替换，否则插入到 IEND 之前。
+    /// 关键字必须是 1-79 个可打印的 Latin-1 字符，文本也必须能以 Latin-1 表示
+    pub fn set_text(&mut self, keyword: &str, text: &str) -> PngResult<()> {
+        let latin1 = |s: &str| {
+            s.chars()
+                .map(|c| u8::try_from(c as u32).ok())
+                .collect::<Option<Vec<u8>>>()
+        };
+        let invalid_keyword = || PngError::InvalidTextKeyword(keyword.to_string());
+        let keyword_bytes = latin1(keyword).ok_or_else(invalid_keyword)?;
+        let printable = |b: &u8| matches!(b, 32..=126 | 161..=255);
+        if !(1..=79).contains(&keyword_bytes.len()) || !keyword_bytes.iter().all(printable) {
+            return Err(invalid_keyword());
         }
 
-        let mut chunks: Vec<Chunk> = Vec::new();
+        let mut data = keyword_bytes.clone();
+        data.push(0);
+        data.extend(latin1(text).ok_or(PngError::NonLatin1Text)?);
 
-        let mut header = [0; 8];
-        let _ = value.read(&mut header);
-        if header != Self::STANDARD_HEADER {
-            return Err(Error::from("icorrect header"));
+        let existing = self.chunks.iter_mut().find(|chunk| {
+            chunk.chunk_type() == "tEXt"
+                && chunk.data().split(|&b| b == 0).next() == Some(keyword_bytes.as_slice())
+        });
+        match existing {
+            Some(chunk) => chunk.set_data(data, CrcPolicy::Recompute)?,
+            None => {
+                let chunk_type = ChunkType::from_bytes_unchecked(*b"tEXt");
+                self.append_chunk(Chunk::try_new(chunk_type, data)?);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn chunk_count(&self) -> usize {
+        self.chunks().len()
+    }
+
+    pub fn chunk_count_by_type(&self, ty: &str) -> usize {
+        self.iter_chunks_by_type(ty).count()
+    }
+
+    /// 统计每种块类型出现的次数，按类型排序
+    pub fn chunk_type_counts(&self) -> BTreeMap<String, usize> {
+        let mut counts = BTreeMap::new();
+        for chunk in self.chunks.iter() {
+            *counts.entry(chunk.chunk_type().to_string()).or_insert(0) += 1;
         }
+        counts
+    }
 
-        while !value.is_empty() {
-            let chunk = Chunk::try_from(value)?;
-            value = &value[chunk.chunk_length()..];
-            chunks.push(chunk);
+    /// 添加一个块。IEND 必须是最后一个块，所以存在结尾的 IEND 时会插入到它前面
+    pub fn append_chunk(&mut self, chunk: Chunk) {
+        match self.chunks.last() {
+            Some(last) if last.chunk_type() == "IEND" => {
+                let index = self.chunks.len() - 1;
+                self.chunks.insert(index, chunk);
+            }
+            _ => self.chunks.push(chunk),
         }
+    }
 
-        Ok(Png { chunks })
+    /// 在 index 位置插入一个块，index 等于块的数量时插入到最后
+    pub fn insert_chunk_at(&mut self, index: usize, chunk: Chunk) -> PngResult<()> {
+        if index > self.chunks.len() {
+            return Err(PngError::IndexOutOfRange {
+                index,
+                len: self.chunks.len(),
+            });
+        }
+        self.chunks.insert(index, chunk);
+        Ok(())
+    }
+
+    /// 在 index 位置插入一个块，但不允许插入到 IHDR 之前或 IEND 之后
+    pub fn insert_chunk(&mut self, index: usize, chunk: Chunk) -> PngResult<()> {
+        let min = match self.chunks.first() {
+            Some(first) if first.chunk_type() == "IHDR" => 1,
+            _ => 0,
+        };
+        let max = self
+            .chunks
+            .iter()
+            .position(|c| c.chunk_type() == "IEND")
+            .unwrap_or(self.chunks.len());
+        if !(min..=max).contains(&index) {
+            return Err(PngError::InvalidInsertPosition { index, min, max });
+        }
+        self.insert_chunk_at(index, chunk)
+    }
+
+    /// 在第一个 IDAT 块前插入一个块，没有 IDAT 时与 `append_chunk` 相同
+    pub fn insert_before_idat(&mut self, chunk: Chunk) {
+        match self.chunks.iter().position(|c| c.chunk_type() == "IDAT") {
+            Some(index) => self.chunks.insert(index, chunk),
+            None => self.append_chunk(chunk),
+        }
+    }
+
+    /// 等价于 `remove_first_chunk_by_type`
+    pub fn remove_chunk(&mut self, s: &str) -> Option<Chunk> {
+        self.remove_first_chunk_by_type(s)
+    }
+
+    /// 按文件中的顺序删除第一个指定类型的块并返回它，调用方可以据此撤销或移动该块
+    pub fn remove_first_chunk_by_type(&mut self, s: &str) -> Option<Chunk> {
+        let index = self
+            .chunks
+            .iter()
+            .position(|chunk| chunk.chunk_type() == s)?;
+        Some(self.chunks.remove(index))
+    }
+
+    /// 删除所有指定类型的块，按文件中的顺序返回被删除的块，其余块的顺序不变
+    pub fn remove_all_chunks_by_type(&mut self, s: &str) -> Vec<Chunk> {
+        let (removed, kept) = std::mem::take(&mut self.chunks)
+            .into_iter()
+            .partition(|chunk| chunk.chunk_type() == s);
+        self.chunks = kept;
+        removed
+    }
+
+    /// 将所有指定类型的块合并为一个，放在第一次出现的位置，返回被合并的块数。
+    /// tEXt 块保留第一个关键字，文本以换行连接；其余类型直接拼接数据
+    pub fn compact(&mut self, s: &str) -> usize {
+        let matches: Vec<usize> = self
+            .chunks
+            .iter()
+            .enumerate()
+            .filter(|(_, chunk)| chunk.chunk_type() == s)
+            .map(|(index, _)| index)
+            .collect();
+        if matches.len() < 2 {
+            return matches.len();
+        }
+
+        let mut data = Vec::new();
+        for (i, &index) in matches.iter().enumerate() {
+            let chunk_data = self.chunks[index].data();
+            if s == "tEXt" {
+                match (i, chunk_data.iter().position(|&b| b == 0)) {
+                    (0, _) | (_, None) => data.extend_from_slice(chunk_data),
+                    (_, Some(sep)) => {
+                        data.push(b'\n');
+                        data.extend_from_slice(&chunk_data[sep + 1..]);
+                    }
+                }
+            } else {
+                data.extend_from_slice(chunk_data);
+            }
+        }
+
+        let first = matches[0];
+        let merged = Chunk::new(*self.chunks[first].chunk_type(), data);
+        for &index in matches[1..].iter().rev() {
+            self.chunks.remove(index);
+        }
+        self.chunks[first] = merged;
+        matches.len()
+    }
+
+    /// 重新计算所有块的 crc，返回被修正的块数
+    pub fn repair_crcs(&mut self) -> usize {
+        self.chunks
+            .iter_mut()
+            .map(Chunk::recompute_crc)
+            .filter(|&changed| changed)
+            .count()
+    }
+
+    /// 删除辅助块，模拟图片编辑器修改图片时的行为，返回被删除的块。
+    /// unsafe_only 为 true 时只删除不可安全复制的辅助块
+    pub fn strip_ancillary(&mut self, unsafe_only: bool) -> Vec<Chunk> {
+        let (removed, kept) = std::mem::take(&mut self.chunks)
+            .into_iter()
+            .partition(|chunk| {
+                let chunk_type = chunk.chunk_type();
+                if chunk_type.is_critical() {
+                    return false;
+                }
+                !unsafe_only || !chunk_type.is_safe_to_copy()
+            });
+        self.chunks = kept;
+        removed
+    }
+}
+
+impl<'a> IntoIterator for &'a Png {
+    type Item = &'a Chunk;
+    type IntoIter = std::slice::Iter<'a, Chunk>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_chunks()
+    }
+}
+
+impl TryFrom<&[u8]> for Png {
+    type Error = PngError;
+
+    fn try_from(value: &[u8]) -> PngResult<Self> {
+        Self::parse(value, false)
     }
 }
 
@@ -126,13 +707,11 @@ mod tests {
     use std::str::FromStr;
 
     fn testing_chunks() -> Vec<Chunk> {
-        let mut chunks = Vec::new();
-
-        chunks.push(chunk_from_strings("FrSt", "I am the first chunk").unwrap());
-        chunks.push(chunk_from_strings("miDl", "I am another chunk").unwrap());
-        chunks.push(chunk_from_strings("LASt", "I am the last chunk").unwrap());
-
-        chunks
+        vec![
+            chunk_from_strings("FrSt", "I am the first chunk").unwrap(),
+            chunk_from_strings("miDl", "I am another chunk").unwrap(),
+            chunk_from_strings("LASt", "I am the last chunk").unwrap(),
+        ]
     }
 
     fn testing_png() -> Png {
@@ -193,6 +772,301 @@ mod tests {
         assert!(png.is_err());
     }
 
+    #[test]
+    fn test_error_context() {
+        // 破坏 RuSt 块的 crc
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let index = png
+            .chunks()
+            .iter()
+            .position(|c| c.chunk_type() == "RuSt")
+            .unwrap();
+        let range = png.byte_range_of(index).unwrap();
+        let mut bytes = PNG_FILE.to_vec();
+        bytes[range.end - 1] ^= 0xff;
+
+        let crc = png.chunks()[index].crc();
+        let expected = format!(
+            "chunk {index} (RuSt) at offset {}: CRC mismatch (stored {:#010x}, computed {crc:#010x})",
+            range.start,
+            crc ^ 0xff
+        );
+        assert_eq!(
+            Png::try_from(bytes.as_slice()).unwrap_err().to_string(),
+            expected
+        );
+        assert_eq!(
+            Png::from_reader(bytes.as_slice()).unwrap_err().to_string(),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_validate() {
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+        assert!(png.validate().is_empty());
+
+        let issues = testing_png().validate();
+        let messages: Vec<String> = issues.iter().map(|i| i.to_string()).collect();
+        assert_eq!(
+            messages,
+            [
+                "error: first chunk is FrSt, expected IHDR",
+                "error: missing IDAT chunk",
+                "error: missing IEND chunk",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate_after_iend() {
+        let mut chunks = Png::try_from(&PNG_FILE[..]).unwrap().chunks().to_vec();
+        chunks.push(chunk_from_strings("IHDR", "again").unwrap());
+        chunks.push(chunk_from_strings("teXt", "late").unwrap());
+        let mut bad_crc = chunks[1].clone();
+        bad_crc
+            .set_data(b"changed".to_vec(), CrcPolicy::Keep)
+            .unwrap();
+        chunks[1] = bad_crc;
+
+        let issues = Png::from_chunks(chunks).validate();
+        let severities: Vec<Severity> = issues.iter().map(|i| i.severity).collect();
+        assert_eq!(
+            severities,
+            [
+                Severity::Error,
+                Severity::Error,
+                Severity::Error,
+                Severity::Warning
+            ]
+        );
+        assert_eq!(issues[0].message, "IHDR appears 2 times, expected once");
+        assert!(issues[1].message.contains("CRC mismatch"));
+        assert!(issues[3].message.ends_with("(teXt) after IEND"));
+    }
+
+    #[test]
+    fn test_duplicated_singletons() {
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+        assert!(png.duplicated_singletons().is_empty());
+
+        let mut chunks = png.chunks().to_vec();
+        chunks.push(chunk_from_strings("IEND", "").unwrap());
+        chunks.push(chunk_from_strings("IEND", "").unwrap());
+        let png = Png::from_chunks(chunks);
+        assert_eq!(png.duplicated_singletons(), [("IEND", 3)]);
+        assert_eq!(
+            png.validate()[0].message,
+            "IEND appears 3 times, expected once"
+        );
+    }
+
+    #[test]
+    fn test_chunk_headers() {
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let headers = Png::chunk_headers(&PNG_FILE).unwrap();
+        let expected: Vec<(ChunkType, usize)> = png
+            .iter_chunks()
+            .map(|c| (*c.chunk_type(), c.length()))
+            .collect();
+        assert_eq!(headers, expected);
+
+        assert!(matches!(
+            Png::chunk_headers(&PNG_FILE[..PNG_FILE.len() - 3]),
+            Err(PngError::Chunk(err)) if matches!(err.source, PngError::ChunkTooShort { .. })
+        ));
+        assert!(matches!(
+            Png::chunk_headers(&PNG_FILE[..4]),
+            Err(PngError::BadSignature)
+        ));
+    }
+
+    #[test]
+    fn test_not_a_png() {
+        let jpeg = [
+            0xff, 0xd8, 0xff, 0xe0, 0x00, 0x10, 0x4a, 0x46, 0x49, 0x46, 0x00,
+        ];
+        let png = Png::try_from(jpeg.as_ref());
+        assert_eq!(
+            png.unwrap_err().to_string(),
+            "not a PNG file: bad signature"
+        );
+    }
+
+    #[test]
+    fn test_truncated_header() {
+        let png = Png::try_from(&Png::STANDARD_HEADER[..5]);
+        assert_eq!(
+            png.unwrap_err().to_string(),
+            "not a PNG file: bad signature"
+        );
+
+        let png = Png::try_from([].as_ref());
+        assert!(png.is_err());
+    }
+
+    #[test]
+    fn test_strict_spec() {
+        let mut png = testing_png();
+        png.append_chunk(chunk_from_strings("Rust", "reserved bit set").unwrap());
+        let bytes = png.as_bytes();
+
+        assert!(Png::try_from(bytes.as_ref()).is_ok());
+        assert!(Png::try_from_strict(bytes.as_ref()).is_err());
+        assert!(Png::try_from_strict(testing_png().as_bytes().as_ref()).is_ok());
+    }
+
+    #[test]
+    fn test_concatenated_png() {
+        let outer = Png::try_from(&PNG_FILE[..]).unwrap();
+        let inner = testing_png();
+        let bytes: Vec<u8> = PNG_FILE
+            .iter()
+            .chain(inner.as_bytes().iter())
+            .copied()
+            .collect();
+
+        // 拼接的文件可以正常解析，内层 PNG 作为尾部数据保留
+        let png = Png::try_from(bytes.as_ref()).unwrap();
+        assert_eq!(png.chunks(), outer.chunks());
+        assert_eq!(png.trailing_data(), inner.as_bytes());
+        assert_eq!(png.as_bytes(), bytes);
+
+        // from_prefix 给出外层 PNG 的长度，剩下的部分就是内层 PNG
+        let (prefix, consumed) = Png::from_prefix(&bytes).unwrap();
+        assert_eq!(consumed, PNG_FILE.len());
+        assert_eq!(prefix.chunks(), outer.chunks());
+        let (extracted, inner_len) = Png::from_prefix(&bytes[consumed..]).unwrap();
+        assert_eq!(inner_len, inner.byte_len());
+        assert_eq!(extracted.chunks(), inner.chunks());
+
+        let headers = Png::chunk_headers(&bytes).unwrap();
+        assert_eq!(headers.len(), outer.chunks().len());
+    }
+
+    #[test]
+    fn test_trailing_data_round_trip() {
+        let mut bytes = PNG_FILE.to_vec();
+        bytes.extend_from_slice(b"\x00\x00\x00\x05junk after IEND");
+
+        let mut png = Png::try_from(bytes.as_slice()).unwrap();
+        assert_eq!(png.chunks(), Png::try_from(&PNG_FILE[..]).unwrap().chunks());
+        assert_eq!(png.trailing_data(), b"\x00\x00\x00\x05junk after IEND");
+        assert_eq!(png.as_bytes(), bytes);
+
+        let png_from_reader = Png::from_reader(bytes.as_slice()).unwrap();
+        assert_eq!(png_from_reader.as_bytes(), bytes);
+
+        // 块头都不完整的短数据
+        let mut short = PNG_FILE.to_vec();
+        short.extend_from_slice(b"abc");
+        assert_eq!(Png::try_from(short.as_slice()).unwrap().as_bytes(), short);
+
+        // 只读取块头时无法通过 crc 区分尾部数据，只有块头不完整时才会停止
+        let headers = Png::chunk_headers(&short).unwrap();
+        assert_eq!(headers.last().unwrap().0, "IEND");
+
+        assert_eq!(
+            png.strip_trailing_data(),
+            b"\x00\x00\x00\x05junk after IEND"
+        );
+        assert!(png.trailing_data().is_empty());
+        assert_eq!(png.as_bytes(), PNG_FILE.to_vec());
+    }
+
+    #[test]
+    fn test_from_reader_lenient() {
+        let mut bytes = PNG_FILE.to_vec();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0xff;
+
+        assert!(Png::from_reader(bytes.as_slice()).is_err());
+        let png = Png::from_reader_lenient(bytes.as_slice()).unwrap();
+        let bad: Vec<String> = png
+            .chunks()
+            .iter()
+            .filter(|c| !c.is_crc_valid())
+            .map(|c| c.chunk_type().to_string())
+            .collect();
+        assert_eq!(bad, ["IEND"]);
+    }
+
+    #[test]
+    fn test_try_from_lenient() {
+        let mut bytes = PNG_FILE.to_vec();
+        // 破坏 IHDR 的 crc
+        bytes[8 + 8 + 13] ^= 0xff;
+
+        assert!(Png::try_from(bytes.as_slice()).is_err());
+        let (png, errors) = Png::try_from_lenient(&bytes).unwrap();
+        assert_eq!(png.chunks().len(), 7);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].index, 0);
+        assert_eq!(errors[0].offset, 8);
+        assert_eq!(errors[0].chunk_type, "IHDR");
+        assert!(matches!(errors[0].source, PngError::CrcMismatch { .. }));
+
+        let (_, errors) = Png::try_from_lenient(&PNG_FILE).unwrap();
+        assert!(errors.is_empty());
+    }
+
+    #[test]
+    fn test_from_reader_unchecked_types() {
+        let mut bytes = Png::STANDARD_HEADER.to_vec();
+        let chunk_type = ChunkType::from_bytes_unchecked(*b"ru5t");
+        bytes.extend(Chunk::new(chunk_type, b"digit".to_vec()).as_bytes());
+
+        assert!(Png::from_reader(bytes.as_slice()).is_err());
+        let png = Png::from_reader_unchecked_types(bytes.as_slice()).unwrap();
+        assert_eq!(png.chunk_by_type("ru5t").unwrap().data(), b"digit");
+    }
+
+    #[test]
+    fn test_repair_crcs() {
+        let mut bytes = PNG_FILE.to_vec();
+        bytes[8 + 8 + 13] ^= 0xff;
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0xff;
+
+        let (mut png, _) = Png::try_from_lenient(&bytes).unwrap();
+        assert_eq!(png.repair_crcs(), 2);
+        assert_eq!(png.repair_crcs(), 0);
+        assert_eq!(png.as_bytes(), PNG_FILE.to_vec());
+    }
+
+    #[test]
+    fn test_has_valid_signature() {
+        assert!(Png::has_valid_signature(&Png::STANDARD_HEADER));
+        assert!(Png::has_valid_signature(&PNG_FILE));
+        assert!(!Png::has_valid_signature(&[
+            0xff, 0xd8, 0xff, 0xe0, 0, 16, 74, 70
+        ]));
+        assert!(!Png::has_valid_signature(&Png::STANDARD_HEADER[..7]));
+    }
+
+    #[test]
+    fn test_from_reader() {
+        let png = Png::from_reader(std::io::Cursor::new(PNG_FILE.to_vec())).unwrap();
+        assert_eq!(png.as_bytes(), PNG_FILE.to_vec());
+    }
+
+    #[test]
+    fn test_from_reader_truncated() {
+        let truncated = &PNG_FILE[..PNG_FILE.len() - 3];
+        assert!(matches!(
+            Png::from_reader(truncated),
+            Err(PngError::Chunk(err)) if matches!(err.source, PngError::ChunkTooShort { .. })
+        ));
+        assert!(matches!(
+            Png::from_reader(&PNG_FILE[..4]),
+            Err(PngError::BadSignature)
+        ));
+        assert!(matches!(
+            Png::from_reader(std::io::empty()),
+            Err(PngError::BadSignature)
+        ));
+    }
+
     #[test]
     fn test_invalid_chunk() {
         let mut chunk_bytes: Vec<u8> = testing_chunks()
@@ -222,6 +1096,15 @@ mod tests {
         assert_eq!(chunks.len(), 3);
     }
 
+    #[test]
+    fn test_chunks_slice_indexing() {
+        let png = testing_png();
+        let chunks = png.chunks();
+        assert_eq!(&chunks[0].chunk_type().to_string(), "FrSt");
+        assert_eq!(&chunks[2].chunk_type().to_string(), "LASt");
+        assert_eq!(chunks[1..].len(), 2);
+    }
+
     #[test]
     fn test_chunk_by_type() {
         let png = testing_png();
@@ -230,6 +1113,142 @@ mod tests {
         assert_eq!(&chunk.data_as_string().unwrap(), "I am the first chunk");
     }
 
+    #[test]
+    fn test_chunks_by_type() {
+        let mut png = testing_png();
+        png.append_chunk(chunk_from_strings("FrSt", "I am the second first chunk").unwrap());
+        let chunks = png.chunks_by_type("FrSt");
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(&chunks[0].data_as_string().unwrap(), "I am the first chunk");
+        assert_eq!(
+            &chunks[1].data_as_string().unwrap(),
+            "I am the second first chunk"
+        );
+        assert!(png.chunks_by_type("NoNe").is_empty());
+    }
+
+    #[test]
+    fn test_iter_chunks() {
+        let png = testing_png();
+        let types: Vec<String> = png
+            .iter_chunks()
+            .map(|c| c.chunk_type().to_string())
+            .collect();
+        assert_eq!(types, ["FrSt", "miDl", "LASt"]);
+
+        let mut count = 0;
+        for chunk in &png {
+            assert_eq!(chunk, &png.chunks()[count]);
+            count += 1;
+        }
+        assert_eq!(count, 3);
+        assert_eq!(
+            (&png)
+                .into_iter()
+                .filter(|c| c.chunk_type() == "miDl")
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_iter_chunks_by_type() {
+        let mut png = testing_png();
+        png.append_chunk(chunk_from_strings("FrSt", "I am the second first chunk").unwrap());
+        let mut chunks = png.iter_chunks_by_type("FrSt");
+        assert_eq!(chunks.next().unwrap().data(), b"I am the first chunk");
+        assert_eq!(
+            chunks.next().unwrap().data(),
+            b"I am the second first chunk"
+        );
+        assert!(chunks.next().is_none());
+        assert_eq!(png.iter_chunks_by_type("NoNe").count(), 0);
+    }
+
+    #[test]
+    fn test_append_chunk_before_iend() {
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        png.append_chunk(chunk_from_strings("TeSt", "Message").unwrap());
+
+        let chunks = png.chunks();
+        assert_eq!(&chunks[chunks.len() - 1].chunk_type().to_string(), "IEND");
+        assert_eq!(&chunks[chunks.len() - 2].chunk_type().to_string(), "TeSt");
+    }
+
+    #[test]
+    fn test_insert_chunk_at() {
+        let mut png = testing_png();
+        png.insert_chunk_at(1, chunk_from_strings("TeSt", "Message").unwrap())
+            .unwrap();
+        assert_eq!(&png.chunks()[1].chunk_type().to_string(), "TeSt");
+
+        png.insert_chunk_at(4, chunk_from_strings("LaSt", "end").unwrap())
+            .unwrap();
+        assert_eq!(&png.chunks()[4].chunk_type().to_string(), "LaSt");
+
+        let err = png.insert_chunk_at(9, chunk_from_strings("TeSt", "x").unwrap());
+        assert!(matches!(
+            err,
+            Err(PngError::IndexOutOfRange { index: 9, len: 5 })
+        ));
+    }
+
+    #[test]
+    fn test_insert_chunk() {
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let len = png.chunks().len();
+
+        // 紧跟在 IHDR 之后
+        png.insert_chunk(1, chunk_from_strings("TeSt", "Message").unwrap())
+            .unwrap();
+        assert_eq!(&png.chunks()[1].chunk_type().to_string(), "TeSt");
+
+        // IEND 之前
+        png.insert_chunk(len, chunk_from_strings("LaSt", "end").unwrap())
+            .unwrap();
+        assert_eq!(&png.chunks()[len].chunk_type().to_string(), "LaSt");
+
+        let err = png.insert_chunk(0, chunk_from_strings("TeSt", "x").unwrap());
+        assert!(matches!(
+            err,
+            Err(PngError::InvalidInsertPosition {
+                index: 0,
+                min: 1,
+                ..
+            })
+        ));
+        let err = png.insert_chunk(len + 2, chunk_from_strings("TeSt", "x").unwrap());
+        assert!(matches!(err, Err(PngError::InvalidInsertPosition { .. })));
+    }
+
+    #[test]
+    fn test_insert_before_idat() {
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        png.insert_before_idat(chunk_from_strings("TeSt", "Message").unwrap());
+
+        let types: Vec<String> = png
+            .chunks()
+            .iter()
+            .map(|c| c.chunk_type().to_string())
+            .collect();
+        let test = types.iter().position(|t| t == "TeSt").unwrap();
+        assert_eq!(types[test + 1], "IDAT");
+
+        let mut png = testing_png();
+        png.insert_before_idat(chunk_from_strings("TeSt", "Message").unwrap());
+        assert_eq!(&png.chunks()[3].chunk_type().to_string(), "TeSt");
+    }
+
+    #[test]
+    fn test_chunk_count() {
+        let mut png = testing_png();
+        png.append_chunk(chunk_from_strings("miDl", "I am a duplicate").unwrap());
+        assert_eq!(png.chunk_count(), 4);
+        assert_eq!(png.chunk_count_by_type("miDl"), 2);
+        assert_eq!(png.chunk_count_by_type("FrSt"), 1);
+        assert_eq!(png.chunk_count_by_type("IDAT"), 0);
+    }
+
     #[test]
     fn test_append_chunk() {
         let mut png = testing_png();
@@ -239,6 +1258,50 @@ mod tests {
         assert_eq!(&chunk.data_as_string().unwrap(), "Message");
     }
 
+    #[test]
+    fn test_compact() {
+        let mut png = Png::from_chunks(vec![
+            chunk_from_strings("IHDR", "header").unwrap(),
+            chunk_from_strings("tEXt", "Comment\0first").unwrap(),
+            chunk_from_strings("IDAT", "data").unwrap(),
+            chunk_from_strings("tEXt", "Author\0second").unwrap(),
+            chunk_from_strings("IEND", "").unwrap(),
+        ]);
+
+        assert_eq!(png.compact("tEXt"), 2);
+        assert_eq!(png.chunks().len(), 4);
+        assert_eq!(&png.chunks()[1].chunk_type().to_string(), "tEXt");
+        assert_eq!(png.chunks()[1].data(), b"Comment\0first\nsecond");
+
+        let mut png = testing_png();
+        png.append_chunk(chunk_from_strings("miDl", " again").unwrap());
+        assert_eq!(png.compact("miDl"), 2);
+        assert_eq!(
+            png.chunk_by_type("miDl").unwrap().data(),
+            b"I am another chunk again"
+        );
+        assert_eq!(png.compact("NoNe"), 0);
+    }
+
+    #[test]
+    fn test_strip_ancillary_unsafe_only() {
+        let mut png = Png::from_chunks(vec![
+            chunk_from_strings("IHDR", "header").unwrap(),
+            chunk_from_strings("saFe", "safe to copy").unwrap(),
+            chunk_from_strings("unSF", "unsafe to copy").unwrap(),
+            chunk_from_strings("IEND", "").unwrap(),
+        ]);
+
+        let removed = png.strip_ancillary(true);
+        assert_eq!(removed.len(), 1);
+        assert_eq!(&removed[0].chunk_type().to_string(), "unSF");
+        assert!(png.chunk_by_type("saFe").is_some());
+
+        let removed = png.strip_ancillary(false);
+        assert_eq!(&removed[0].chunk_type().to_string(), "saFe");
+        assert_eq!(png.chunks().len(), 2);
+    }
+
     #[test]
     fn test_remove_chunk() {
         let mut png = testing_png();
@@ -248,6 +1311,184 @@ mod tests {
         assert!(chunk.is_none());
     }
 
+    #[test]
+    fn test_remove_chunk_first_only() {
+        let mut png = testing_png();
+        png.append_chunk(chunk_from_strings("miDl", "I am a duplicate").unwrap());
+
+        let removed = png.remove_chunk("miDl").unwrap();
+        assert_eq!(removed.data(), b"I am another chunk");
+        assert_eq!(png.chunk_count_by_type("miDl"), 1);
+    }
+
+    #[test]
+    fn test_remove_first_and_all_with_two_rust_chunks() {
+        let rust = |data| chunk_from_strings("ruSt", data).unwrap();
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        png.append_chunk(rust("one"));
+        png.append_chunk(rust("two"));
+
+        let mut first = Png::from_chunks(png.chunks().to_vec());
+        assert_eq!(first.remove_first_chunk_by_type("ruSt"), Some(rust("one")));
+        assert_eq!(first.chunks_by_type("ruSt"), [&rust("two")]);
+
+        let removed = png.remove_all_chunks_by_type("ruSt");
+        assert_eq!(removed, [rust("one"), rust("two")]);
+        assert_eq!(png.as_bytes(), PNG_FILE.to_vec());
+    }
+
+    #[test]
+    fn test_remove_all_chunks_by_type() {
+        let mut png = testing_png();
+        png.append_chunk(chunk_from_strings("miDl", "I am a duplicate").unwrap());
+
+        let removed = png.remove_all_chunks_by_type("miDl");
+        assert_eq!(removed.len(), 2);
+        assert_eq!(removed[0].data(), b"I am another chunk");
+        assert_eq!(removed[1].data(), b"I am a duplicate");
+        assert!(png.chunk_by_type("miDl").is_none());
+        assert!(png.remove_all_chunks_by_type("miDl").is_empty());
+        assert_eq!(png.chunks().len(), 2);
+    }
+
+    #[test]
+    fn test_byte_range_of() {
+        let png = testing_png();
+        let chunks = png.chunks();
+
+        let first = png.byte_range_of(0).unwrap();
+        assert_eq!(first, 8..8 + chunks[0].chunk_length());
+
+        let second = png.byte_range_of(1).unwrap();
+        assert_eq!(second.start, first.end);
+        assert_eq!(second.len(), chunks[1].chunk_length());
+
+        let bytes = png.as_bytes();
+        assert_eq!(&bytes[second], chunks[1].as_bytes().as_slice());
+        assert!(png.byte_range_of(3).is_none());
+    }
+
+    #[test]
+    fn test_chunk_type_counts() {
+        let mut png = testing_png();
+        png.append_chunk(chunk_from_strings("miDl", "I am a duplicate").unwrap());
+        let counts = png.chunk_type_counts();
+        assert_eq!(counts.len(), 3);
+        assert_eq!(counts["miDl"], 2);
+        assert_eq!(counts["FrSt"], 1);
+    }
+
+    #[test]
+    fn test_dimensions() {
+        #[rustfmt::skip]
+        let ihdr_data = vec![
+            0, 0, 1, 0,     // width
+            0, 0, 0, 200,   // height
+            8, 6, 0, 0, 0,  // bit depth, color type, compression, filter, interlace
+        ];
+        let ihdr = Chunk::new(ChunkType::from_str("IHDR").unwrap(), ihdr_data);
+        let png = Png::from_chunks(vec![ihdr]);
+        assert_eq!(png.dimensions(), Some((256, 200)));
+
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+        assert_eq!(png.dimensions(), Some((50, 50)));
+    }
+
+    #[test]
+    fn test_dimensions_without_ihdr() {
+        let png = testing_png();
+        assert!(png.ihdr().is_none());
+        assert_eq!(png.dimensions(), None);
+
+        let short = Chunk::new(ChunkType::from_str("IHDR").unwrap(), vec![0, 0, 1]);
+        let png = Png::from_chunks(vec![short]);
+        assert_eq!(png.dimensions(), None);
+    }
+
+    #[test]
+    fn test_header() {
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let header = png.header().unwrap();
+        assert_eq!((header.width, header.height), (50, 50));
+        assert_eq!(header.bit_depth, 8);
+        assert_eq!(header.interlace, 0);
+
+        let png = testing_png();
+        assert!(matches!(png.header(), Err(PngError::MissingIhdr)));
+
+        let short = Chunk::new(ChunkType::from_str("IHDR").unwrap(), vec![0, 0, 1]);
+        let png = Png::from_chunks(vec![short]);
+        assert!(matches!(png.header(), Err(PngError::MalformedIhdr(3))));
+    }
+
+    #[test]
+    fn test_set_text() {
+        let mut png = testing_png();
+        png.set_text("Author", "Jane Doe").unwrap();
+        png.set_text("Comment", "café").unwrap();
+        png.set_text("Author", "John Doe").unwrap();
+
+        assert_eq!(png.chunk_count_by_type("tEXt"), 2);
+        assert_eq!(
+            png.text_chunks(),
+            vec![
+                ("Author".to_string(), "John Doe".to_string()),
+                ("Comment".to_string(), "café".to_string()),
+            ]
+        );
+        assert_eq!(png.chunks_by_type("tEXt")[1].data(), b"Comment\0caf\xe9");
+    }
+
+    #[test]
+    fn test_set_text_invalid() {
+        let mut png = testing_png();
+        let long = "k".repeat(80);
+        for keyword in ["", long.as_str(), "tab\there", "日本"] {
+            assert!(matches!(
+                png.set_text(keyword, "value"),
+                Err(PngError::InvalidTextKeyword(_))
+            ));
+        }
+        assert!(matches!(
+            png.set_text("Title", "日本"),
+            Err(PngError::NonLatin1Text)
+        ));
+        assert_eq!(png.chunk_count_by_type("tEXt"), 0);
+    }
+
+    #[test]
+    fn test_text_chunks() {
+        let mut png = testing_png();
+        let text = |data: &[u8]| Chunk::new(ChunkType::from_str("tEXt").unwrap(), data.to_vec());
+        png.append_chunk(text(b"Author\0Jane Doe"));
+        png.append_chunk(text(b"no separator"));
+        png.append_chunk(text(b"Comment\0caf\xe9"));
+
+        assert_eq!(
+            png.text_chunks(),
+            vec![
+                ("Author".to_string(), "Jane Doe".to_string()),
+                ("Comment".to_string(), "café".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_text_chunks_split_at_first_null() {
+        let mut png = testing_png();
+        let text = |data: &[u8]| Chunk::new(ChunkType::from_str("tEXt").unwrap(), data.to_vec());
+        png.append_chunk(text(b"Title\0a\0b"));
+        png.append_chunk(text(b"\0empty keyword"));
+
+        assert_eq!(
+            png.text_chunks(),
+            vec![
+                ("Title".to_string(), "a\0b".to_string()),
+                (String::new(), "empty keyword".to_string()),
+            ]
+        );
+    }
+
     #[test]
     fn test_png_from_image_file() {
         let png = Png::try_from(&PNG_FILE[..]);
@@ -258,10 +1499,20 @@ mod tests {
     fn test_as_bytes() {
         let png = Png::try_from(&PNG_FILE[..]).unwrap();
         let actual = png.as_bytes();
-        let expected: Vec<u8> = PNG_FILE.iter().copied().collect();
+        let expected: Vec<u8> = PNG_FILE.to_vec();
         assert_eq!(actual, expected);
     }
 
+    #[test]
+    fn test_write_to() {
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        png.append_chunk(chunk_from_strings("ruSt", "hidden").unwrap());
+        let mut written = Vec::new();
+        png.write_to(&mut written).unwrap();
+        assert_eq!(written, png.as_bytes());
+        assert_eq!(png.byte_len(), written.len());
+    }
+
     #[test]
     fn test_png_trait_impls() {
         let chunk_bytes: Vec<u8> = testing_chunks()
@@ -0,0 +1,94 @@
+//! 破坏性操作（encode/remove/strip）的审计报告，以 JSON 格式写入文件
+
+use std::{
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use pngme::{chunk::Chunk, Result};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+#[derive(Debug, Serialize)]
+pub struct Report {
+    command: Vec<String>,
+    /// Unix 时间戳（秒）
+    timestamp: u64,
+    input: PathBuf,
+    output: PathBuf,
+    before: FileDigest,
+    after: FileDigest,
+    added: Vec<ChunkSummary>,
+    removed: Vec<ChunkSummary>,
+}
+
+#[derive(Debug, Serialize)]
+struct FileDigest {
+    size: usize,
+    sha256: String,
+}
+
+#[derive(Debug, Serialize)]
+struct ChunkSummary {
+    #[serde(rename = "type")]
+    chunk_type: String,
+    length: usize,
+}
+
+impl Report {
+    /// before 和 after 分别为修改前输入文件和修改后输出文件的内容
+    pub fn new(input: &Path, before: &[u8], output: &Path, after: &[u8]) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        Report {
+            command: std::env::args().collect(),
+            timestamp,
+            input: input.to_path_buf(),
+            output: output.to_path_buf(),
+            before: FileDigest::new(before),
+            after: FileDigest::new(after),
+            added: Vec::new(),
+            removed: Vec::new(),
+        }
+    }
+
+    pub fn added<'a>(mut self, chunks: impl IntoIterator<Item = &'a Chunk>) -> Self {
+        self.added.extend(chunks.into_iter().map(ChunkSummary::new));
+        self
+    }
+
+    pub fn removed<'a>(mut self, chunks: impl IntoIterator<Item = &'a Chunk>) -> Self {
+        self.removed
+            .extend(chunks.into_iter().map(ChunkSummary::new));
+        self
+    }
+
+    pub fn write(&self, path: &Path) -> Result<()> {
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+impl FileDigest {
+    fn new(content: &[u8]) -> Self {
+        let sha256 = Sha256::digest(content)
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect();
+        FileDigest {
+            size: content.len(),
+            sha256,
+        }
+    }
+}
+
+impl ChunkSummary {
+    fn new(chunk: &Chunk) -> Self {
+        ChunkSummary {
+            chunk_type: chunk.chunk_type().to_string(),
+            length: chunk.length(),
+        }
+    }
+}
@@ -0,0 +1,253 @@
+use std::process::Command;
+
+use pngme::{Chunk, ChunkType, Png};
+
+fn testing_png_file(name: &str, chunks: &[(&str, &str)]) -> std::path::PathBuf {
+    let chunks = chunks
+        .iter()
+        .map(|(ty, data)| Chunk::new(ty.parse::<ChunkType>().unwrap(), data.as_bytes().to_vec()))
+        .collect();
+    let path = std::env::temp_dir().join(format!("pngme-exit-{}-{name}.png", std::process::id()));
+    std::fs::write(&path, Png::from_chunks(chunks).as_bytes()).unwrap();
+    path
+}
+
+fn pngme(args: &[&str]) -> Option<i32> {
+    Command::new(env!("CARGO_BIN_EXE_pngme"))
+        .args(args)
+        .output()
+        .unwrap()
+        .status
+        .code()
+}
+
+#[test]
+fn test_exit_code_when_message_missing() {
+    let path = testing_png_file("missing", &[("IHDR", "header"), ("IEND", "")]);
+    let path_str = path.to_str().unwrap();
+    let decode = pngme(&["decode", path_str, "-c", "ruSt"]);
+    let check = pngme(&["check", path_str]);
+    let bad_path = pngme(&["decode", "/nonexistent/pngme.png", "-c", "ruSt"]);
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(decode, Some(2));
+    assert_eq!(check, Some(2));
+    assert_eq!(bad_path, Some(1));
+}
+
+#[test]
+fn test_exit_code_when_message_found() {
+    let path = testing_png_file("found", &[("IHDR", "header"), ("ruSt", "hi"), ("IEND", "")]);
+    let path_str = path.to_str().unwrap();
+    let decode = pngme(&["decode", path_str, "-c", "ruSt"]);
+    let check = pngme(&["check", path_str]);
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(decode, Some(0));
+    assert_eq!(check, Some(0));
+}
+
+#[test]
+fn test_check_output() {
+    let missing = testing_png_file("check-missing", &[("IHDR", "header"), ("IEND", "")]);
+    let found = testing_png_file(
+        "check-found",
+        &[
+            ("IHDR", "header"),
+            ("ruSt", "a"),
+            ("abCd", "b"),
+            ("ruSt", "c"),
+            ("IEND", ""),
+        ],
+    );
+    let stdout = |path: &std::path::Path| {
+        let output = Command::new(env!("CARGO_BIN_EXE_pngme"))
+            .args(["check", path.to_str().unwrap()])
+            .output()
+            .unwrap();
+        String::from_utf8(output.stdout).unwrap()
+    };
+    let (missing_output, found_output) = (stdout(&missing), stdout(&found));
+    std::fs::remove_file(&missing).unwrap();
+    std::fs::remove_file(&found).unwrap();
+
+    assert_eq!(missing_output, "no secret message found\n");
+    assert_eq!(found_output, "secret message found: ruSt, abCd\n");
+}
+
+#[test]
+fn test_error_message_has_chunk_context() {
+    let path = testing_png_file(
+        "bad-crc",
+        &[("IHDR", "0123456789abc"), ("IDAT", "data"), ("IEND", "")],
+    );
+    let mut bytes = std::fs::read(&path).unwrap();
+    // IDAT 的 crc 位于 8 + 25 + 8 + 4 之后
+    bytes[8 + 25 + 8 + 4] ^= 0xff;
+    std::fs::write(&path, bytes).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_pngme"))
+        .args(["print", path.to_str().unwrap()])
+        .output()
+        .unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert!(
+        stderr.contains("chunk 1 (IDAT) at offset 33: CRC mismatch"),
+        "{stderr}"
+    );
+}
+
+#[test]
+fn test_status_goes_to_stderr_when_writing_stdout() {
+    let path = testing_png_file(
+        "strip-stdout",
+        &[("IHDR", "header"), ("tEXt", "comment"), ("IEND", "")],
+    );
+    let output = Command::new(env!("CARGO_BIN_EXE_pngme"))
+        .args(["strip", path.to_str().unwrap(), "-o", "-"])
+        .output()
+        .unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    let stripped = Png::try_from(output.stdout.as_slice()).unwrap();
+    assert!(stripped.chunk_by_type("tEXt").is_none());
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "1 chunks removed, 19 bytes saved\n"
+    );
+}
+
+#[test]
+fn test_emit_report_reads_stdin_once() {
+    let path = testing_png_file(
+        "report-stdin",
+        &[("IHDR", "header"), ("tEXt", "comment"), ("IEND", "")],
+    );
+    let report = path.with_extension("json");
+    let output = Command::new(env!("CARGO_BIN_EXE_pngme"))
+        .args(["strip", "-", "--emit-report", report.to_str().unwrap()])
+        .stdin(std::fs::File::open(&path).unwrap())
+        .output()
+        .unwrap();
+    let before = std::fs::read(&path).unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&std::fs::read(&report).unwrap()).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    std::fs::remove_file(&report).unwrap();
+
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(json["before"]["size"], before.len());
+    assert_eq!(json["after"]["size"], output.stdout.len());
+    assert_eq!(json["removed"][0]["type"], "tEXt");
+}
+
+#[test]
+fn test_remove_all_reports_count() {
+    let path = testing_png_file(
+        "remove-all",
+        &[
+            ("IHDR", "header"),
+            ("ruSt", "a"),
+            ("ruSt", "b"),
+            ("IEND", ""),
+        ],
+    );
+    let remove_all = || {
+        let output = Command::new(env!("CARGO_BIN_EXE_pngme"))
+            .args([
+                "remove",
+                path.to_str().unwrap(),
+                "-c",
+                "ruSt",
+                "--all",
+                "--no-backup",
+            ])
+            .output()
+            .unwrap();
+        (
+            output.status.code(),
+            String::from_utf8(output.stdout).unwrap(),
+        )
+    };
+    let (first, second) = (remove_all(), remove_all());
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(first.0, Some(0));
+    assert!(first.1.starts_with("removed ruSt"), "{}", first.1);
+    assert!(
+        first.1.ends_with("`ruSt` removed 2 message(s)\n"),
+        "{}",
+        first.1
+    );
+    assert_eq!(
+        second,
+        (Some(0), "`ruSt` removed 0 message(s)\n".to_string())
+    );
+}
+
+#[test]
+fn test_list_size_includes_trailing_data() {
+    let path = testing_png_file("list-trailing", &[("IHDR", "header"), ("IEND", "")]);
+    let mut bytes = std::fs::read(&path).unwrap();
+    bytes.extend_from_slice(b"junk");
+    std::fs::write(&path, &bytes).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_pngme"))
+        .args(["list", path.to_str().unwrap()])
+        .output()
+        .unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        stdout.ends_with(&format!("2 chunks, {} bytes\n", bytes.len())),
+        "{stdout}"
+    );
+}
+
+#[test]
+fn test_encode_stdin_without_output_writes_only_png() {
+    let path = testing_png_file("encode-stdin", &[("IHDR", "header"), ("IEND", "")]);
+    let output = Command::new(env!("CARGO_BIN_EXE_pngme"))
+        .args(["encode", "-", "-c", "ruSt", "-m", "hi"])
+        .stdin(std::fs::File::open(&path).unwrap())
+        .output()
+        .unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(output.status.code(), Some(0));
+    let png = Png::try_from(output.stdout.as_slice()).unwrap();
+    assert!(png.trailing_data().is_empty());
+    assert_eq!(png.chunk_by_type("ruSt").unwrap().data(), b"hi");
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .starts_with("wrote 1 ruSt chunk"));
+}
+
+#[test]
+fn test_concatenated_png_extracts_inner_png() {
+    let inner = Png::from_chunks(vec![
+        Chunk::new("IHDR".parse().unwrap(), b"inner".to_vec()),
+        Chunk::new("IEND".parse().unwrap(), Vec::new()),
+    ]);
+    let path = testing_png_file("concatenated", &[("IHDR", "outer"), ("IEND", "")]);
+    let mut bytes = std::fs::read(&path).unwrap();
+    bytes.extend_from_slice(&inner.as_bytes());
+    std::fs::write(&path, &bytes).unwrap();
+    let extracted = path.with_extension("inner.png");
+
+    let print = pngme(&["print", path.to_str().unwrap()]);
+    let extract = pngme(&[
+        "extract-trailing-png",
+        path.to_str().unwrap(),
+        "-o",
+        extracted.to_str().unwrap(),
+    ]);
+    let extracted_bytes = std::fs::read(&extracted).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    std::fs::remove_file(&extracted).unwrap();
+
+    assert_eq!(print, Some(0));
+    assert_eq!(extract, Some(0));
+    assert_eq!(extracted_bytes, inner.as_bytes());
+}